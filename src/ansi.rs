@@ -0,0 +1,511 @@
+//! Text and ANSI art exports and imports, one character per tile.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::Color;
+
+use crate::grid::Tile;
+use crate::palette::{TileColor, TilePalette};
+use crate::project::ProjectGrid;
+use crate::tilesets::{CP437, Tileset};
+use crate::util;
+
+/// Color escape sequences of ANSI exports.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AnsiColors {
+    /// 24-bit `38;2;r;g;b`.
+    TrueColor,
+    /// Nearest of the 240 fixed xterm colors, `38;5;n`.
+    Xterm256,
+}
+
+/// Colors of `.ans` files in SGR order, the bright ones are bold or blinking.
+const VGA: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (170, 0, 0), (0, 170, 0), (170, 85, 0),
+    (0, 0, 170), (170, 0, 170), (0, 170, 170), (170, 170, 170),
+    (85, 85, 85), (255, 85, 85), (85, 255, 85), (255, 255, 85),
+    (85, 85, 255), (255, 85, 255), (85, 255, 255), (255, 255, 255),
+];
+
+fn rgb(color: TileColor, palette: &TilePalette) -> Option<(u8, u8, u8)> {
+    if color == TileColor::Transparent { return None; }
+    let [r, g, b, _] = palette.color(color).as_rgba_f32().map(|c| (c * 255.).round() as u8);
+    Some((r, g, b))
+}
+
+fn nearest(colors: impl Iterator<Item=(usize, (u8, u8, u8))>, (r, g, b): (u8, u8, u8)) -> usize {
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        (r as i32 - r2 as i32).pow(2) + (g as i32 - g2 as i32).pow(2) + (b as i32 - b2 as i32).pow(2)
+    };
+    colors.min_by_key(|(_, color)| distance(*color)).map_or(0, |(i, _)| i)
+}
+
+/// The 6x6x6 cube and the grays, the 16 first colors depend on the terminal.
+fn xterm_rgb(i: usize) -> (u8, u8, u8) {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    if i >= 232 {
+        let v = (8 + 10 * (i - 232)) as u8;
+        return (v, v, v);
+    }
+    let i = i - 16;
+    (LEVELS[i / 36], LEVELS[i / 6 % 6], LEVELS[i % 6])
+}
+
+fn sgr(color: TileColor, palette: &TilePalette, background: bool, colors: AnsiColors) -> String {
+    let Some(rgb) = rgb(color, palette) else { return if background { "49" } else { "39" }.to_string() };
+    let layer = if background { 48 } else { 38 };
+    match colors {
+        AnsiColors::TrueColor => format!("{};2;{};{};{}", layer, rgb.0, rgb.1, rgb.2),
+        AnsiColors::Xterm256 => format!("{};5;{}", layer, nearest((16..256).map(|i| (i, xterm_rgb(i))), rgb)),
+    }
+}
+
+fn tiles(grid: &ProjectGrid) -> HashMap<(isize, isize), Tile> {
+    grid.flatten().into_iter().collect()
+}
+
+/// Plain UTF-8 text, glyphs without a character become spaces.
+pub fn to_text(grid: &ProjectGrid, tileset: &Tileset) -> String {
+    let tiles = tiles(grid);
+    let mut text = String::new();
+    for y in grid.y0..grid.y0 + grid.h as isize {
+        let line = (grid.x0..grid.x0 + grid.w as isize)
+            .map(|x| tiles.get(&(x, y)).and_then(|tile| tileset.charmap.char(tile.index)).unwrap_or(' '))
+            .collect::<String>();
+        text += line.trim_end();
+        text.push('\n');
+    }
+    text
+}
+
+/// UTF-8 text with color escape sequences, glyphs without a character become spaces.
+pub fn to_ansi(grid: &ProjectGrid, palette: &TilePalette, tileset: &Tileset, colors: AnsiColors) -> String {
+    let tiles = tiles(grid);
+    let mut text = String::new();
+    for y in grid.y0..grid.y0 + grid.h as isize {
+        let mut current = None;
+        for x in grid.x0..grid.x0 + grid.w as isize {
+            let Some(tile) = tiles.get(&(x, y)) else {
+                if current.take().is_some() { text += "\x1b[0m"; }
+                text.push(' ');
+                continue;
+            };
+            if current != Some((tile.fg, tile.bg)) {
+                text += &format!("\x1b[{};{}m", sgr(tile.fg, palette, false, colors), sgr(tile.bg, palette, true, colors));
+                current = Some((tile.fg, tile.bg));
+            }
+            text.push(tileset.charmap.char(tile.index).unwrap_or(' '));
+        }
+        text += "\x1b[0m\n";
+    }
+    text
+}
+
+/// Byte of a character in code page 437, `?` for the ones it lacks or that terminals would interpret.
+pub fn cp437_byte(c: char) -> u8 {
+    if c == ' ' { return b' '; }
+    match CP437.iter().position(|d| *d == c) {
+        Some(i) if ![0x00, 0x07, 0x08, 0x09, 0x0a, 0x0d, 0x1a, 0x1b].contains(&i) => i as u8,
+        _ => b'?',
+    }
+}
+
+/// `.ans` file: code page 437 with 16 colors, bright backgrounds use iCE colors.
+/// Ends with a SAUCE record giving the size of the drawing.
+pub fn to_ans(grid: &ProjectGrid, palette: &TilePalette, tileset: &Tileset, title: &str) -> Vec<u8> {
+    let tiles = tiles(grid);
+    let vga = |color: TileColor, default: usize| rgb(color, palette)
+        .map_or(default, |rgb| nearest(VGA.iter().copied().enumerate(), rgb));
+
+    let mut bytes = vec![];
+    for y in grid.y0..grid.y0 + grid.h as isize {
+        let mut current = None;
+        for x in grid.x0..grid.x0 + grid.w as isize {
+            let tile = tiles.get(&(x, y));
+            let (fg, bg) = tile.map_or((7, 0), |t| (vga(t.fg, 7), vga(t.bg, 0)));
+            if current != Some((fg, bg)) {
+                let bold = if fg > 7 { "1;" } else { "" };
+                let blink = if bg > 7 { "5;" } else { "" };
+                bytes.extend(format!("\x1b[0;{}{}3{};4{}m", bold, blink, fg % 8, bg % 8).bytes());
+                current = Some((fg, bg));
+            }
+            bytes.push(tile.and_then(|t| tileset.charmap.char(t.index)).map_or(b' ', cp437_byte));
+        }
+        bytes.extend(b"\x1b[0m");
+        // 80 column rows wrap on their own
+        if grid.w != 80 { bytes.extend(b"\r\n"); }
+    }
+
+    let size = bytes.len();
+    bytes.push(0x1a);
+    bytes.extend(sauce(title, size, grid.w, grid.h));
+    bytes
+}
+
+/// 128 bytes record of a character file, with iCE colors and the VGA font.
+fn sauce(title: &str, size: usize, w: usize, h: usize) -> Vec<u8> {
+    let field = |text: &str, len: usize| {
+        let mut field = text.chars().map(cp437_byte).take(len).collect::<Vec<u8>>();
+        field.resize(len, b' ');
+        field
+    };
+
+    let mut record = b"SAUCE00".to_vec();
+    record.extend(field(title, 35));
+    record.extend(field("", 20));
+    record.extend(field("", 20));
+    record.extend(date().bytes());
+    record.extend((size as u32).to_le_bytes());
+    // Character, ANSi
+    record.extend([1, 1]);
+    record.extend((w as u16).to_le_bytes());
+    record.extend((h as u16).to_le_bytes());
+    record.extend([0; 4]);
+    // No comments, iCE colors
+    record.extend([0, 1]);
+    let mut font = b"IBM VGA".to_vec();
+    font.resize(22, 0);
+    record.extend(font);
+    record
+}
+
+/// Today as `CCYYMMDD`, from Howard Hinnant's days to civil date algorithm.
+fn date() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / 86400) as i64;
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}", y, m, d)
+}
+
+/// SGR color while reading ANSI art.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum AnsiColor {
+    Default,
+    /// 16 VGA colors, then the xterm ones.
+    Index(usize),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Attributes {
+    fg: AnsiColor,
+    bg: AnsiColor,
+    bold: bool,
+    blink: bool,
+    inverse: bool,
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes { fg: AnsiColor::Default, bg: AnsiColor::Default, bold: false, blink: false, inverse: false }
+    }
+}
+
+impl Attributes {
+    /// Bold brightens the foreground and blinking the background, like iCE colors.
+    fn colors(&self) -> ((u8, u8, u8), (u8, u8, u8)) {
+        let rgb = |color: AnsiColor, default: usize, bright: bool| match color {
+            AnsiColor::Default => VGA[default + if bright { 8 } else { 0 }],
+            AnsiColor::Index(i) if i < 8 && bright => VGA[i + 8],
+            AnsiColor::Index(i) if i < 16 => VGA[i],
+            AnsiColor::Index(i) => xterm_rgb(i.min(255)),
+            AnsiColor::Rgb(r, g, b) => (r, g, b),
+        };
+        let (fg, bg) = (rgb(self.fg, 7, self.bold), rgb(self.bg, 0, self.blink));
+        if self.inverse { (bg, fg) } else { (fg, bg) }
+    }
+
+    /// `38;5;n` and `38;2;r;g;b` take the next parameters.
+    fn color(params: &mut impl Iterator<Item=usize>) -> AnsiColor {
+        match params.next() {
+            Some(5) => AnsiColor::Index(params.next().unwrap_or(0)),
+            Some(2) => {
+                let mut channel = || params.next().unwrap_or(0).min(255) as u8;
+                AnsiColor::Rgb(channel(), channel(), channel())
+            }
+            _ => AnsiColor::Default,
+        }
+    }
+
+    fn apply(&mut self, params: &[usize]) {
+        if params.is_empty() { *self = Attributes::default(); }
+        let mut params = params.iter().copied();
+        while let Some(p) = params.next() {
+            match p {
+                0 => *self = Attributes::default(),
+                1 => self.bold = true,
+                5 | 6 => self.blink = true,
+                7 => self.inverse = true,
+                22 => self.bold = false,
+                25 => self.blink = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = AnsiColor::Index(p - 30),
+                38 => self.fg = Attributes::color(&mut params),
+                39 => self.fg = AnsiColor::Default,
+                40..=47 => self.bg = AnsiColor::Index(p - 40),
+                48 => self.bg = Attributes::color(&mut params),
+                49 => self.bg = AnsiColor::Default,
+                90..=97 => self.fg = AnsiColor::Index(p - 90 + 8),
+                100..=107 => self.bg = AnsiColor::Index(p - 100 + 8),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Width and height of the SAUCE record of character files.
+fn read_sauce(bytes: &[u8]) -> Option<(usize, usize)> {
+    let record = bytes.get(bytes.len().checked_sub(128)?..)?;
+    if &record[..7] != b"SAUCE00" || record[94] != 1 { return None; }
+    let w = u16::from_le_bytes([record[96], record[97]]) as usize;
+    let h = u16::from_le_bytes([record[98], record[99]]) as usize;
+    Some((w, h))
+}
+
+/// Reads ANSI art or text: SGR colors, cursor movements, and code page 437 for `.ans` files
+/// or anything that isn't UTF-8. Code page 437 lines wrap at 80 columns or at the SAUCE width.
+/// Characters are mapped with the charmap of the tileset and colors to the nearest of the palette.
+pub fn from_ansi(bytes: &[u8], cp437: bool, palette: &TilePalette, tileset: &Tileset) -> Result<(usize, usize, Vec<((isize, isize), Tile)>), String> {
+    let sauce = read_sauce(bytes);
+    let content = if sauce.is_some() { &bytes[..bytes.len() - 128] } else { bytes };
+    let content = &content[..content.iter().position(|b| *b == 0x1a).unwrap_or(content.len())];
+
+    // Controls stay controls, other low bytes are glyphs in code page 437
+    let utf8 = std::str::from_utf8(content).ok().filter(|_| !cp437 && sauce.is_none());
+    let chars = match utf8 {
+        Some(text) => text.chars().collect::<Vec<char>>(),
+        None => {
+            content.iter()
+                .map(|&b| if [0x09, 0x0a, 0x0d, 0x1b].contains(&b) { b as char } else { CP437[b as usize] })
+                .collect()
+        }
+    };
+    let wrap = if utf8.is_some() { None } else { Some(sauce.map_or(80, |(w, _)| w).max(1)) };
+
+    let max = util::size::MAX_GRID;
+    let mut cells: HashMap<(usize, usize), (char, Attributes)> = HashMap::new();
+    let (mut x, mut y, mut saved) = (0usize, 0usize, (0, 0));
+    let mut attributes = Attributes::default();
+    let mut chars = chars.into_iter().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                let mut sequence = String::new();
+                let mut command = None;
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        command = Some(c);
+                        break;
+                    }
+                    sequence.push(c);
+                }
+                let params = sequence.trim_start_matches('?').split(';')
+                    .map(|p| p.parse::<usize>().unwrap_or(0))
+                    .collect::<Vec<usize>>();
+                // Positions stay within the biggest grid
+                let n = params.first().copied().unwrap_or(0).clamp(1, max);
+                match command {
+                    Some('m') => attributes.apply(if sequence.is_empty() { &[] } else { &params }),
+                    Some('A') => y = y.saturating_sub(n),
+                    Some('B') => y = (y + n).min(max),
+                    Some('C') => x = (x + n).min(max),
+                    Some('D') => x = x.saturating_sub(n),
+                    Some('H') | Some('f') => {
+                        y = params.first().copied().unwrap_or(1).clamp(1, max) - 1;
+                        x = params.get(1).copied().unwrap_or(1).clamp(1, max) - 1;
+                    }
+                    Some('s') => saved = (x, y),
+                    Some('u') => (x, y) = saved,
+                    Some('J') if params.first() == Some(&2) => {
+                        cells.clear();
+                        (x, y) = (0, 0);
+                    }
+                    Some('K') => cells.retain(|&(cx, cy), _| cy != y || cx < x),
+                    _ => {}
+                }
+            }
+            '\r' => x = 0,
+            '\n' => (x, y) = (0, (y + 1).min(max)),
+            '\t' => x = ((x / 8 + 1) * 8).min(max),
+            c if c.is_control() => {}
+            c => {
+                if wrap.map_or(false, |w| x >= w) { (x, y) = (0, (y + 1).min(max)); }
+                if x < max && y < max { cells.insert((x, y), (c, attributes)); }
+                x = (x + 1).min(max);
+            }
+        }
+    }
+
+    let (w, h) = cells.keys().fold(sauce.unwrap_or((0, 0)), |(w, h), (x, y)| (w.max(x + 1), h.max(y + 1)));
+    let (w, h) = (w.min(max), h.min(max));
+    if w == 0 || h == 0 { return Err("nothing to import".to_string()); }
+
+    let blank_tile = tileset.blank();
+    let color = |(r, g, b): (u8, u8, u8)| palette.nearest(Color::rgb_u8(r, g, b));
+    let blank = Attributes::default().colors();
+    let tiles = (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (c, (fg, bg)) = cells.get(&(x, y)).map_or((' ', blank), |(c, a)| (*c, a.colors()));
+            let index = if c == ' ' { blank_tile } else { tileset.charmap.glyph(c).unwrap_or(blank_tile) };
+            ((x as isize, y as isize), Tile { bg: color(bg), fg: color(fg), index, flip: (false, false), rotation: 0 })
+        })
+        .collect();
+    Ok((w, h, tiles))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::ProjectLayer;
+
+    fn import(bytes: &[u8], cp437: bool) -> (usize, usize, HashMap<(isize, isize), usize>) {
+        let (w, h, tiles) = from_ansi(bytes, cp437, &TilePalette::default(), &Tileset::default()).unwrap();
+        (w, h, tiles.into_iter().map(|(pos, tile)| (pos, tile.index)).collect())
+    }
+
+    fn glyph(c: char) -> usize {
+        Tileset::default().charmap.glyph(c).unwrap()
+    }
+
+    #[test]
+    fn bold_and_blink_brighten_colors() {
+        let mut attributes = Attributes::default();
+        attributes.apply(&[1, 31, 5, 44]);
+        assert_eq!(attributes.colors(), (VGA[9], VGA[12]));
+
+        attributes.apply(&[22, 25]);
+        assert_eq!(attributes.colors(), (VGA[1], VGA[4]));
+
+        attributes.apply(&[7]);
+        assert_eq!(attributes.colors(), (VGA[4], VGA[1]));
+
+        attributes.apply(&[]);
+        assert_eq!(attributes.colors(), (VGA[7], VGA[0]));
+    }
+
+    #[test]
+    fn extended_colors() {
+        let mut attributes = Attributes::default();
+        attributes.apply(&[38, 2, 1, 2, 3, 48, 5, 231]);
+        assert_eq!(attributes.colors(), ((1, 2, 3), (255, 255, 255)));
+
+        // Missing channels are 0
+        attributes.apply(&[0, 38, 2, 999]);
+        assert_eq!(attributes.colors(), ((255, 0, 0), VGA[0]));
+    }
+
+    #[test]
+    fn cursor_moves() {
+        let (w, h, tiles) = import(b"A\x1b[2CB\x1b[3;5HC\x1b[2AD", false);
+        assert_eq!((w, h), (6, 3));
+        assert_eq!(tiles[&(0, 0)], glyph('A'));
+        assert_eq!(tiles[&(3, 0)], glyph('B'));
+        assert_eq!(tiles[&(4, 2)], glyph('C'));
+        assert_eq!(tiles[&(5, 0)], glyph('D'));
+        assert_eq!(import(b"A\x1b[10DE", false).2[&(0, 0)], glyph('E'));
+    }
+
+    #[test]
+    fn cursor_stays_in_the_biggest_grid() {
+        let max = util::size::MAX_GRID;
+        let (w, h, tiles) = import(b"\x1b[99999999;99999999HA\x1b[99999999BB\x1b[99999999CC\nD", false);
+        assert_eq!((w, h), (max, max));
+        assert_eq!(tiles[&(max as isize - 1, max as isize - 1)], glyph('A'));
+        assert!(from_ansi(b"\x1b[18446744073709551615C\x1b[18446744073709551615B", false, &TilePalette::default(), &Tileset::default()).is_err());
+    }
+
+    #[test]
+    fn cp437_lines_wrap() {
+        let (w, h, tiles) = import(&[b'A'; 81], true);
+        assert_eq!((w, h), (80, 2));
+        assert_eq!(tiles[&(0, 1)], glyph('A'));
+
+        // 0xdb is the full block
+        assert_eq!(CP437[0xdb], '█');
+        assert_eq!(cp437_byte('█'), 0xdb);
+        assert_eq!(cp437_byte('\u{1b}'), b'?');
+    }
+
+    #[test]
+    fn sauce_width_wraps_lines() {
+        let mut bytes = vec![b'A'; 10];
+        bytes.push(0x1a);
+        bytes.extend(sauce("test", 10, 4, 5));
+        let (w, h, tiles) = import(&bytes, false);
+        assert_eq!((w, h), (4, 5));
+        assert_eq!(tiles[&(1, 2)], glyph('A'));
+        assert!(!tiles.contains_key(&(0, 5)));
+    }
+
+    fn dos_tileset(charmap: &str) -> Tileset {
+        Tileset::parse(&format!(
+            "(name: \"DOS\", image: \"dos.png\", tile_width: 8, tile_height: 16, columns: 16, rows: 32, charmap: {})",
+            charmap,
+        )).unwrap()
+    }
+
+    #[test]
+    fn cp437_charmap() {
+        let charmap = dos_tileset(r#"(tables: [(0, "cp437")], fallback: Some(63))"#).charmap;
+        assert_eq!((charmap.tile('▓'), charmap.tile('é')), (Some(0xb2), Some(0x82)));
+        assert_eq!((charmap.char(0xb2), charmap.char(0x82)), (Some('▓'), Some('é')));
+        assert_eq!((charmap.tile('a'), charmap.tile('A')), (Some(0x61), Some(0x41)));
+        assert_eq!((charmap.tile('€'), charmap.glyph('€')), (None, Some(63)));
+        assert_eq!((charmap.char(0), charmap.char(300)), (None, None));
+
+        // Runs come first, lowercase letters can use the uppercase glyphs
+        let charmap = dos_tileset(r#"(runs: [(300, "▓")], tables: [(0, "cp437")], uppercase: true)"#).charmap;
+        assert_eq!((charmap.tile('▓'), charmap.char(0xb2), charmap.char(300)), (Some(300), Some('▓'), Some('▓')));
+        assert_eq!((charmap.tile('a'), charmap.tile('é'), charmap.glyph('€')), (Some(0x41), Some(0x90), None));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let tileset = dos_tileset(r#"(tables: [(0, "cp437")])"#);
+        let palette = TilePalette::default();
+        let indices = [0xb2, 0x82, 0x61, 0x41];
+        let tiles = indices.iter().enumerate()
+            .map(|(x, index)| ((x as isize, 0), Tile { index: *index, ..Default::default() }))
+            .collect();
+        let grid = ProjectGrid::new(0, 0, 4, 1, vec![ProjectLayer::new("Background", tiles)]);
+
+        let text = to_text(&grid, &tileset);
+        assert_eq!(text, "▓éaA\n");
+        let ans = to_ans(&grid, &palette, &tileset, "test");
+        for (bytes, cp437) in [(text.as_bytes(), false), (ans.as_slice(), true)] {
+            let (w, h, tiles) = from_ansi(bytes, cp437, &palette, &tileset).unwrap();
+            assert_eq!((w, h), (4, 1));
+            assert_eq!(tiles.iter().map(|(_, tile)| tile.index).collect::<Vec<usize>>(), indices);
+        }
+    }
+
+    #[test]
+    fn sauce_record_offsets() {
+        let record = sauce("Title", 300, 132, 40);
+        assert_eq!(record.len(), 128);
+        assert_eq!(&record[..7], b"SAUCE00");
+        assert_eq!(&record[7..12], b"Title");
+        assert_eq!(u32::from_le_bytes([record[90], record[91], record[92], record[93]]), 300);
+        assert_eq!((record[94], record[95]), (1, 1));
+        assert_eq!(record[105], 1);
+        assert_eq!(&record[106..113], b"IBM VGA");
+        assert_eq!(read_sauce(&record), Some((132, 40)));
+
+        let mut binary = record.clone();
+        binary[94] = 5;
+        assert_eq!(read_sauce(&binary), None);
+        assert_eq!(read_sauce(&record[1..]), None);
+        assert_eq!(read_sauce(b"SAUCE00"), None);
+    }
+}
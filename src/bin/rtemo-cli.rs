@@ -0,0 +1,90 @@
+//! Converts drawings without opening a window:
+//! `rtemo-cli drawing.rtemo drawing.png --scale 4`
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use rtemo::convert::{self, Format, PngOptions};
+
+const USAGE: &str = "\
+Usage: rtemo-cli <input> <output> [options]
+
+Formats are guessed from the file extensions:
+  tuples (.ron)        tile list copied by the export tool
+  rtemo                project file
+  png                  image, output only
+  text (.txt)          plain UTF-8 text
+  ansi                 text with 24-bit color escape sequences
+  ansi256              text with 256 color escape sequences
+  ans                  code page 437 ANSI art with a SAUCE record
+
+Options:
+  --from <format>  format of the input
+  --to <format>    format of the output
+  --scale <n>      upscale PNG images n times
+  --opaque         draw transparent colors over black in PNG images
+  -h, --help       show this message";
+
+struct Args {
+    input: PathBuf,
+    from: Format,
+    output: PathBuf,
+    to: Format,
+    options: PngOptions,
+}
+
+fn parse_args(args: impl Iterator<Item=String>) -> Result<Args, String> {
+    let mut paths = vec![];
+    let (mut from, mut to) = (None, None);
+    let mut options = PngOptions::default();
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" | "--to" => {
+                let name = args.next().ok_or(format!("{} needs a format", arg))?;
+                let format = Format::from_name(&name).ok_or(format!("unknown format `{}`", name))?;
+                if arg == "--from" { from = Some(format) } else { to = Some(format) }
+            }
+            "--scale" => {
+                options.scale = args.next()
+                    .and_then(|n| n.parse::<u32>().ok())
+                    .filter(|n| *n > 0)
+                    .ok_or("--scale needs a positive integer")?;
+            }
+            "--opaque" => options.transparent = false,
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [input, output] = <[PathBuf; 2]>::try_from(paths).map_err(|_| "expected an input and an output")?;
+    let from = from.or(Format::from_path(&input)).ok_or(format!("unknown format for {}", input.display()))?;
+    let to = to.or(Format::from_path(&output)).ok_or(format!("unknown format for {}", output.display()))?;
+
+    Ok(Args { input, from, output, to, options })
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let args = match parse_args(args.into_iter()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("rtemo-cli: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match convert::convert(&args.input, args.from, &args.output, args.to, &args.options) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rtemo-cli: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
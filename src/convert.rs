@@ -0,0 +1,136 @@
+//! Conversions between drawing formats that don't need a window, used by `rtemo-cli`.
+
+use std::fs;
+use std::path::Path;
+
+use crate::ansi::{self, AnsiColors};
+use crate::grid::Tile;
+use crate::palette::TilePalette;
+use crate::project::{Project, ProjectGrid, ProjectLayer};
+use crate::quick_tiles::Selection;
+use crate::render;
+use crate::tilesets::Tileset;
+use crate::tools;
+
+pub use crate::render::PngOptions;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Format {
+    /// Tuple list copied by the export tool.
+    Tuples,
+    /// Native `.rtemo` project.
+    Project,
+    Png,
+    /// Plain UTF-8 text.
+    Text,
+    /// Text with 24-bit color escape sequences.
+    Ansi,
+    /// Text with 256 color escape sequences.
+    Ansi256,
+    /// Code page 437 ANSI art with a SAUCE record.
+    Ans,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "tuples" | "ron" => Some(Format::Tuples),
+            "rtemo" | "project" => Some(Format::Project),
+            "png" => Some(Format::Png),
+            "text" | "txt" | "plain" | "utf8" => Some(Format::Text),
+            "ansi" => Some(Format::Ansi),
+            "ansi256" => Some(Format::Ansi256),
+            "ans" => Some(Format::Ans),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        Format::from_name(&path.extension()?.to_string_lossy())
+    }
+}
+
+pub fn convert(input: &Path, from: Format, output: &Path, to: Format, options: &PngOptions) -> Result<(), String> {
+    let project = read(input, from)?;
+    write(&project, output, to, options)
+}
+
+fn read(path: &Path, format: Format) -> Result<Project, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let text = || std::str::from_utf8(&bytes).map_err(|e| e.to_string());
+    match format {
+        Format::Tuples => from_tuples(text()?),
+        Format::Project => Project::parse(text()?),
+        Format::Png => Err(format!("{:?} can't be read", format)),
+        _ => from_ansi(&bytes, format == Format::Ans),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Tilesets missing from the assets folder are replaced by the default one.
+fn write(project: &Project, path: &Path, format: Format, options: &PngOptions) -> Result<(), String> {
+    let palette = project.tile_palette()?;
+    let tileset = Tileset::find(&project.tileset).unwrap_or_default();
+    let grid = &project.grid;
+    let bytes = match format {
+        Format::Project => project.serialize()?.into_bytes(),
+        Format::Png => return render::save_png(grid, &palette, &tileset, path, options),
+        _ => export(grid, &palette, &tileset, format, &title(path))?,
+    };
+    fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Name of the drawing in `.ans` files.
+pub fn title(path: &Path) -> String {
+    path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Tuple and text exports, projects and PNG images are written by [convert].
+pub fn export(grid: &ProjectGrid, palette: &TilePalette, tileset: &Tileset, format: Format, title: &str) -> Result<Vec<u8>, String> {
+    Ok(match format {
+        Format::Tuples => to_tuples(grid, palette).into_bytes(),
+        Format::Text => ansi::to_text(grid, tileset).into_bytes(),
+        Format::Ansi => ansi::to_ansi(grid, palette, tileset, AnsiColors::TrueColor).into_bytes(),
+        Format::Ansi256 => ansi::to_ansi(grid, palette, tileset, AnsiColors::Xterm256).into_bytes(),
+        Format::Ans => ansi::to_ans(grid, palette, tileset, title),
+        Format::Project | Format::Png => return Err(format!("{:?} isn't a text format", format)),
+    })
+}
+
+/// Same layout as the import tool: `y` goes up, the grid starts at (0, 0).
+/// Tuples don't say which tileset and palette they use, the default ones are assumed.
+fn from_tuples(text: &str) -> Result<Project, String> {
+    let (palette, tileset) = (TilePalette::default(), Tileset::default());
+    let tuples = tools::parse_tuples(text, tileset.count(), palette.tuple_count()).map_err(|e| e.to_string())?;
+
+    let w = tuples.iter().map(|t| t.0 + 1).max().ok_or("no tiles")?;
+    let h = tuples.iter().map(|t| t.1 + 1).max().ok_or("no tiles")?;
+
+    let tiles = tuples.iter()
+        .map(|t| ((t.0 as isize, (h - 1 - t.1) as isize), Tile {
+            bg: palette.from_tuple(t.3),
+            fg: palette.from_tuple(t.4),
+            index: t.2,
+            flip: (t.5, t.7),
+            rotation: t.6,
+        }))
+        .collect::<Vec<((isize, isize), Tile)>>();
+
+    let grid = ProjectGrid::new(0, 0, w, h, vec![ProjectLayer::new("Background", tiles)]);
+    Ok(Project::new(grid, Selection::default(), &palette, &tileset))
+}
+
+/// Text and ANSI art use the default tileset and palette.
+fn from_ansi(bytes: &[u8], cp437: bool) -> Result<Project, String> {
+    let (palette, tileset) = (TilePalette::default(), Tileset::default());
+    let (w, h, tiles) = ansi::from_ansi(bytes, cp437, &palette, &tileset)?;
+    let grid = ProjectGrid::new(0, 0, w, h, vec![ProjectLayer::new("Background", tiles)]);
+    Ok(Project::new(grid, Selection::default(), &palette, &tileset))
+}
+
+fn to_tuples(grid: &ProjectGrid, palette: &TilePalette) -> String {
+    tools::to_tuples(grid.flatten().iter().map(|((x, y), tile)| {
+        ((x - grid.x0) as usize, (grid.h as isize - 1 - (y - grid.y0)) as usize, tile)
+    }), palette)
+}
@@ -1,16 +1,22 @@
+use std::ops::Range;
+
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 use bevy::utils::HashMap;
 use bevy_text_mode::{TextModeSpriteSheetBundle, TextModeTextureAtlasSprite};
+use serde::{Deserialize, Serialize};
 
-use crate::{AppState, HEIGHT, util, WIDTH};
-use crate::loading::Textures;
+use crate::{AppState, util};
 use crate::mouse::{ButtonId, Clickable};
+use crate::palette::{Colored, TileColor};
 use crate::quick_tiles::Selection;
+use crate::screen::Screen;
+use crate::tilesets::{Tilesets, TilesetSprite};
 use crate::toolbar::SelectedTool;
 use crate::tools::Tools;
 use crate::util::Palette;
-use crate::util::size::LEFT_MARGIN;
+use crate::util::size::{BOTTOM_MARGIN, LEFT_MARGIN};
+use crate::zoom::Panning;
 
 pub struct GridPlugin;
 
@@ -20,10 +26,13 @@ impl Plugin for GridPlugin {
             .add_event::<GridChanged>()
             .add_event::<GridResized>()
             .add_event::<ZoomChanged>()
+            .add_event::<LayersChanged>()
             .insert_resource(Zoom(1.5))
+            .insert_resource(Pan(Vec2::ZERO))
+            .insert_resource(HoveredTile(None))
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
             .add_systems(
-                (update_hover_tile, update_grid, resize_grid)
+                (update_hovered, update_hover_tile, update_grid, update_layers, update_view)
                 .in_set(OnUpdate(AppState::Editor))
             )
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
@@ -33,20 +42,42 @@ impl Plugin for GridPlugin {
 #[derive(Component)]
 struct GridUI;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Tile {
-    pub bg: Palette,
-    pub fg: Palette,
+    pub bg: TileColor,
+    pub fg: TileColor,
     pub index: usize,
     pub flip: (bool, bool),
     pub rotation: u8,
 }
 
+impl Tile {
+    /// Cell of an upper layer that lets the layers below show through.
+    pub fn empty() -> Self {
+        Tile {
+            bg: TileColor::Transparent,
+            fg: TileColor::Transparent,
+            ..default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bg == TileColor::Transparent && (self.fg == TileColor::Transparent || self.index == 0)
+    }
+
+    /// The tile as seen on top of [below].
+    pub fn over(&self, below: &Tile) -> Tile {
+        if self.is_empty() { return below.clone(); }
+        if self.bg != TileColor::Transparent { return self.clone(); }
+        Tile { bg: below.bg, ..self.clone() }
+    }
+}
+
 impl Default for Tile {
     fn default() -> Self {
         Tile {
-            bg: Palette::E,
-            fg: Palette::A,
+            bg: Palette::E.into(),
+            fg: Palette::A.into(),
             index: 0,
             flip: (false, false),
             rotation: 0,
@@ -57,18 +88,106 @@ impl Default for Tile {
 #[derive(Component)]
 pub struct TilePos(pub (usize, usize));
 
+pub type TileMap = HashMap<(isize, isize), Tile>;
+
+pub struct Layer {
+    /// Stays the same when layers are reordered.
+    pub id: u32,
+    pub name: String,
+    pub visible: bool,
+    pub alpha: f32,
+    /// One tile per position of the grid.
+    pub tiles: TileMap,
+    /// Entities of the visible tiles, see [Grid::view].
+    sprites: HashMap<(isize, isize), Entity>,
+}
+
+/// Layers are drawn from first to last, tools act on the [Grid::active] one.
 #[derive(Resource)]
 pub struct Grid {
     pub x0: isize,
     pub y0: isize,
     pub w: usize,
     pub h: usize,
-    pub tiles: HashMap<(isize, isize), (Tile, Entity)>,
+    pub layers: Vec<Layer>,
+    pub active: usize,
+    /// Clickable entities, one per visible position.
+    pub cells: HashMap<(isize, isize), Entity>,
+    next_id: u32,
+}
+
+impl Grid {
+    pub fn tiles(&self) -> &TileMap {
+        &self.layers[self.active].tiles
+    }
+
+    pub fn tiles_mut(&mut self) -> &mut TileMap {
+        &mut self.layers[self.active].tiles
+    }
+
+    pub fn new_layer(&mut self, name: &str) -> Layer {
+        self.next_id += 1;
+        Layer {
+            id: self.next_id,
+            name: name.to_string(),
+            visible: true,
+            alpha: 1.,
+            tiles: HashMap::new(),
+            sprites: HashMap::new(),
+        }
+    }
+
+    pub fn layer_index(&self, id: u32) -> Option<usize> {
+        self.layers.iter().position(|l| l.id == id)
+    }
+
+    /// Content of new and erased cells.
+    pub fn blank(&self, layer: usize) -> Tile {
+        if layer == 0 { Tile::default() } else { Tile::empty() }
+    }
+
+    pub fn contains(&self, (x, y): (isize, isize)) -> bool {
+        x >= self.x0 && x < self.x0 + self.w as isize && y >= self.y0 && y < self.y0 + self.h as isize
+    }
+
+    /// Positions fully inside the drawing area of the screen, the only ones with entities.
+    pub fn view(&self, layout: Layout) -> (Range<isize>, Range<isize>) {
+        let cell = layout.cell;
+        let left = grid_x(self.x0, self.x0, self.w, layout);
+        let top = grid_y(self.y0, self.y0, self.h, layout) + cell.y;
+        let x_start = ((LEFT_MARGIN - left) / cell.x).ceil().max(0.) as isize;
+        let x_end = (((layout.screen.x - left) / cell.x).floor() as isize).clamp(x_start, self.w as isize);
+        let y_start = ((top - layout.screen.y) / cell.y).ceil().max(0.) as isize;
+        let y_end = (((top - BOTTOM_MARGIN) / cell.y).floor() as isize).clamp(y_start, self.h as isize);
+        (self.x0 + x_start..self.x0 + x_end, self.y0 + y_start..self.y0 + y_end)
+    }
+
+    /// Despawns the entities of a layer about to be dropped.
+    pub fn despawn_layer(layer: &Layer, commands: &mut Commands) {
+        layer.sprites.values().for_each(|id| commands.entity(*id).despawn_recursive());
+    }
+
+    pub fn layer_z(layer: usize) -> f32 {
+        util::z::GRID + util::z::LAYER_STEP * layer as f32
+    }
+
+    /// Despawns every layer, leaving the grid without any.
+    pub fn clear_layers(&mut self, commands: &mut Commands) {
+        for layer in self.layers.drain(..) {
+            Grid::despawn_layer(&layer, commands);
+        }
+        self.active = 0;
+    }
 }
 
 #[derive(Resource)]
 pub struct Zoom(pub f32);
 
+/// Offset of the grid from the center of the drawing area.
+#[derive(Resource)]
+pub struct Pan(pub Vec2);
+
+/// Sent when [Zoom] or [Pan] change.
 pub struct ZoomChanged;
 
 pub struct GridResized;
@@ -84,26 +203,62 @@ pub struct HoverTileIndexOverride {
     pub force_y: Option<isize>,
 }
 
+/// Positions changed in any layer.
 pub struct GridChanged(pub Vec<(isize, isize)>);
 
-fn grid_x(x: isize, x0: isize, w: usize, zoom: f32) -> f32 { return -4. * zoom + LEFT_MARGIN + (WIDTH - LEFT_MARGIN - 8. * zoom * w as f32) / 2. + 8. * zoom * (x - x0) as f32 }
-fn grid_y(y: isize, y0: isize, h: usize, zoom: f32) -> f32 { return -8. * zoom + HEIGHT - (HEIGHT - 8. * zoom * h as f32 - util::size::BOTTOM_MARGIN) / 2. - 8. * zoom * (y - y0) as f32 }
+/// Layers were added, removed, reordered or their visibility or alpha changed.
+pub struct LayersChanged;
+
+/// Grid position under the cursor.
+#[derive(Resource)]
+pub struct HoveredTile(pub Option<(isize, isize)>);
+
+/// Where grid positions are drawn.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Layout {
+    /// Size of a position on screen, the tiles of the active tileset zoomed.
+    pub cell: Vec2,
+    pub scale: f32,
+    pub pan: Vec2,
+    /// Size of the [Screen].
+    pub screen: Vec2,
+}
+
+impl Layout {
+    pub fn new(tilesets: &Tilesets, zoom: &Zoom, pan: &Pan, screen: &Screen) -> Self {
+        Layout { cell: tilesets.active().tile_size() * zoom.0, scale: zoom.0, pan: pan.0, screen: screen.size() }
+    }
+}
+
+pub fn grid_x(x: isize, x0: isize, w: usize, layout: Layout) -> f32 {
+    let cell = layout.cell.x;
+    layout.pan.x - cell / 2. + LEFT_MARGIN + (layout.screen.x - LEFT_MARGIN - cell * w as f32) / 2. + cell * (x - x0) as f32
+}
+
+pub fn grid_y(y: isize, y0: isize, h: usize, layout: Layout) -> f32 {
+    let cell = layout.cell.y;
+    layout.pan.y - cell + layout.screen.y - (layout.screen.y - cell * h as f32 - BOTTOM_MARGIN) / 2. - cell * (y - y0) as f32
+}
 
 fn setup(
     mut commands: Commands,
     mut grid_resized: EventWriter<GridResized>,
     zoom: Res<Zoom>,
-    textures: Res<Textures>,
+    tilesets: Res<Tilesets>,
 ) {
-    let mut tiles: HashMap<(isize, isize), (Tile, Entity)> = HashMap::new();
-
-    commands.insert_resource(Grid {
+    let mut grid = Grid {
         x0: 0,
         y0: 0,
         w: util::size::GRID_X,
         h: util::size::GRID_Y,
-        tiles,
-    });
+        layers: vec![],
+        active: 0,
+        cells: HashMap::new(),
+        next_id: 0,
+    };
+    let layer = grid.new_layer("Background");
+    grid.layers.push(layer);
+    commands.insert_resource(grid);
     grid_resized.send(GridResized);
 
     // Hover tile
@@ -113,7 +268,7 @@ fn setup(
                 anchor: Anchor::BottomLeft,
                 ..Default::default()
             },
-            texture_atlas: textures.mrmotext.clone(),
+            texture_atlas: tilesets.atlas(),
             transform: Transform {
                 translation: Vec3::new(0., 0., util::z::GRID_HOVER),
                 scale: Vec3::new(zoom.0, zoom.0, 1.),
@@ -121,21 +276,38 @@ fn setup(
             },
             ..Default::default()
         })
+        .insert(Colored { bg: Palette::E.into(), fg: Palette::A.into() })
+        .insert(TilesetSprite)
         .insert(HoverTile)
         .insert(GridUI);
 }
 
+fn update_hovered(
+    mut hovered_tile: ResMut<HoveredTile>,
+    panning: Option<Res<Panning>>,
+    hovered: Query<&Clickable, (With<crate::mouse::Hover>, With<GridTile>)>,
+) {
+    let hovered = hovered.iter().find_map(|c| match c.id {
+        ButtonId::Grid(x, y) if panning.is_none() => Some((x, y)),
+        _ => None,
+    });
+    if hovered_tile.0 != hovered { hovered_tile.0 = hovered; }
+}
+
 fn update_hover_tile(
     selection: Res<Selection>,
     tool: Res<SelectedTool>,
     keys: Res<Input<KeyCode>>,
     grid: Res<Grid>,
     zoom: Res<Zoom>,
+    pan: Res<Pan>,
+    screen: Res<Screen>,
+    tilesets: Res<Tilesets>,
     index_override: Option<Res<HoverTileIndexOverride>>,
-    mut hover_tile: Query<(&mut TextModeTextureAtlasSprite, &mut Visibility, &mut Transform), With<HoverTile>>,
+    mut hover_tile: Query<(&mut TextModeTextureAtlasSprite, &mut Colored, &mut Visibility, &mut Transform), With<HoverTile>>,
     hovered: Query<&Transform, (With<crate::mouse::Hover>, With<GridUI>, Without<HoverTile>)>
 ) {
-    if let Ok((mut tile, mut visibility, mut position)) = hover_tile.get_single_mut() {
+    if let Ok((mut tile, mut colored, mut visibility, mut position)) = hover_tile.get_single_mut() {
         let mut new_vis = Visibility::Inherited;
         let mut force_x = None;
         let mut force_y = None;
@@ -143,21 +315,23 @@ fn update_hover_tile(
         if let Some(index) = index_override {
             tile.index = index.index;
             tile.flip_x = false;
+            tile.flip_y = false;
             tile.rotation = 0;
             new_vis = index.visible;
             force_x = index.force_x;
             force_y = index.force_y;
         } else {
             tile.index = if tool.0 == Tools::Eraser { 0 } else { selection.index };
-            tile.flip_x = selection.flip;
+            tile.flip_x = selection.flip.0;
+            tile.flip_y = selection.flip.1;
             tile.rotation = selection.rotation;
         }
 
-        tile.bg = selection.bg.color();
-        tile.fg = selection.fg.color();
+        colored.bg = selection.bg;
+        colored.fg = selection.fg;
 
         visibility.set_if_neq(Visibility::Hidden);
-        if !keys.pressed(KeyCode::LShift) {
+        if !keys.pressed(KeyCode::LShift) && tool.0 != Tools::Select && tool.0 != Tools::Pick {
             for pos in hovered.iter() {
                 visibility.set_if_neq(new_vis);
                 position.translation.x = pos.translation.x;
@@ -168,76 +342,159 @@ fn update_hover_tile(
 
         if let (Some(x), Some(y)) = (force_x, force_y) {
             visibility.set_if_neq(new_vis);
-            position.translation.x = grid_x(x, grid.x0, grid.w, zoom.0);
-            position.translation.y = grid_y(y, grid.y0, grid.h, zoom.0);
-            position.scale.x = zoom.0;
-            position.scale.y = zoom.0;
+            let layout = Layout::new(&tilesets, &zoom, &pan, &screen);
+            position.translation.x = grid_x(x, grid.x0, grid.w, layout);
+            position.translation.y = grid_y(y, grid.y0, grid.h, layout);
         }
+        position.scale.x = zoom.0;
+        position.scale.y = zoom.0;
     }
 }
 
 #[derive(Component)]
 struct GridTile;
 
-fn resize_grid(
+/// Keeps one tile per position and layer inside the grid, and entities
+/// for the visible ones, when the grid, its layers or the zoom change.
+fn update_view(
     mut commands: Commands,
     mut grid_resized: EventReader<GridResized>,
-    textures: Res<Textures>,
+    mut layers_changed: EventReader<LayersChanged>,
+    mut zoom_changed: EventReader<ZoomChanged>,
+    mut grid_changed: EventWriter<GridChanged>,
+    tilesets: Res<Tilesets>,
     grid: Option<ResMut<Grid>>,
     zoom: Res<Zoom>,
-    mut transform: Query<&mut Transform, With<GridTile>>,
+    pan: Res<Pan>,
+    screen: Res<Screen>,
+    mut transform: Query<(&mut Transform, Option<&mut Clickable>), Or<(With<GridTile>, With<LayerTile>)>>,
 ) { let Some(mut grid) = grid else { return; };
 
-    if !grid_resized.is_empty() {
-        grid_resized.clear();
-
-        let h = grid.h as isize;
-        let w = grid.w as isize;
-
-        // Despawn OOB tiles
-        let mut to_remove = Vec::new();
-        for (&(x, y), (_, id)) in grid.tiles.iter() {
-            if x < grid.x0 || x >= grid.x0 + w || y < grid.y0 || y >= grid.y0 + h {
-                commands.entity(*id).despawn_recursive();
-                to_remove.push((x, y));
+    if grid_resized.is_empty() && layers_changed.is_empty() && zoom_changed.is_empty() { return; }
+    grid_resized.clear();
+    layers_changed.clear();
+    zoom_changed.clear();
+
+    let (x0, y0, w, h) = (grid.x0, grid.y0, grid.w as isize, grid.h as isize);
+
+    // Drop OOB tiles, blank new positions
+    let mut changed = Vec::new();
+    for layer in 0..grid.layers.len() {
+        let blank = grid.blank(layer);
+        let inside = |&(x, y): &(isize, isize)| x >= x0 && x < x0 + w && y >= y0 && y < y0 + h;
+        let tiles = &mut grid.layers[layer].tiles;
+        tiles.retain(|pos, _| {
+            if inside(pos) { return true; }
+            changed.push(*pos);
+            false
+        });
+        if tiles.len() == (w * h) as usize { continue }
+
+        for y in y0..(y0 + h) {
+            for x in x0..(x0 + w) {
+                if tiles.contains_key(&(x, y)) { continue }
+                tiles.insert((x, y), blank.clone());
+                changed.push((x, y));
             }
         }
-        to_remove.iter().for_each(|i| { grid.tiles.remove(i); });
+    }
 
-        // Update tiles positions
-        for (&(x, y), (_, id)) in grid.tiles.iter() {
-            let Ok(mut transform) = transform.get_mut(*id) else { continue };
-            transform.translation.x = grid_x(x, grid.x0, grid.w, zoom.0);
-            transform.translation.y = grid_y(y, grid.y0, grid.h, zoom.0);
+    // Despawn entities out of view
+    let layout = Layout::new(&tilesets, &zoom, &pan, &screen);
+    let (xs, ys) = grid.view(layout);
+    let visible = |(x, y): &(isize, isize)| xs.contains(x) && ys.contains(y);
+    grid.cells.retain(|pos, id| {
+        if visible(pos) { return true; }
+        commands.entity(*id).despawn_recursive();
+        false
+    });
+    for layer in grid.layers.iter_mut() {
+        layer.sprites.retain(|pos, id| {
+            if visible(pos) { return true; }
+            commands.entity(*id).despawn_recursive();
+            false
+        });
+    }
+
+    // Update positions and sizes
+    let entities = grid.cells.iter()
+        .map(|(pos, id)| (*pos, *id))
+        .chain(grid.layers.iter().flat_map(|l| l.sprites.iter().map(|(pos, id)| (*pos, *id))));
+    for ((x, y), id) in entities {
+        let Ok((mut transform, clickable)) = transform.get_mut(id) else { continue };
+        transform.translation.x = grid_x(x, grid.x0, grid.w, layout);
+        transform.translation.y = grid_y(y, grid.y0, grid.h, layout);
+        match clickable {
+            Some(mut clickable) => {
+                clickable.w = layout.cell.x;
+                clickable.h = layout.cell.y;
+            }
+            None => transform.scale = Vec3::new(zoom.0, zoom.0, 1.),
         }
+    }
 
-        // Spawn missing tiles
-        for y in grid.y0..(grid.y0 + h) {
-            for x in grid.x0..(grid.x0 + w) {
-                if grid.tiles.contains_key(&(x, y)) { continue }
+    // Spawn entities coming into view
+    for y in ys.clone() {
+        for x in xs.clone() {
+            if !grid.cells.contains_key(&(x, y)) {
+                let id = spawn_cell(&mut commands, x, y, &grid, layout);
+                grid.cells.insert((x, y), id);
+            }
 
-                let id = spawn_tile(&mut commands, x, y, &Tile::default(), &grid, &textures, &zoom);
-                grid.tiles.insert((x, y), (Tile::default(), id));
+            for layer in 0..grid.layers.len() {
+                if grid.layers[layer].sprites.contains_key(&(x, y)) { continue }
+                let Some(tile) = grid.layers[layer].tiles.get(&(x, y)) else { continue };
+                let id = spawn_tile(&mut commands, x, y, tile, layer, &grid, tilesets.atlas(), layout);
+                grid.layers[layer].sprites.insert((x, y), id);
             }
         }
     }
+    grid_changed.send(GridChanged(changed));
+}
+
+/// Invisible clickable area of a grid position, shared by all layers.
+fn spawn_cell(
+    commands: &mut Commands,
+    x: isize,
+    y: isize,
+    grid: &Grid,
+    layout: Layout,
+) -> Entity {
+    commands
+        .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+            grid_x(x, grid.x0, grid.w, layout),
+            grid_y(y, grid.y0, grid.h, layout),
+            util::z::GRID,
+        )))
+        .insert(Clickable {
+            w: layout.cell.x,
+            h: layout.cell.y,
+            id: ButtonId::Grid(x, y),
+            hover_click: true,
+        })
+        .insert(GridUI)
+        .insert(GridTile)
+        .id()
 }
 
-pub fn spawn_tile(
+#[derive(Component)]
+struct LayerTile;
+
+fn spawn_tile(
     commands: &mut Commands,
     x: isize,
     y: isize,
     tile: &Tile,
-    grid: &ResMut<Grid>,
-    textures: &Res<Textures>,
-    zoom: &Res<Zoom>,
+    layer: usize,
+    grid: &Grid,
+    atlas: Handle<TextureAtlas>,
+    layout: Layout,
 ) -> Entity {
+    let (alpha, visible) = grid.layers.get(layer).map_or((1., true), |l| (l.alpha, l.visible));
     commands
         .spawn(TextModeSpriteSheetBundle {
             sprite: TextModeTextureAtlasSprite {
-                bg: tile.bg.color(),
-                fg: tile.fg.color(),
-                alpha: 1.,
+                alpha,
                 index: tile.index,
                 anchor: Anchor::BottomLeft,
                 rotation: tile.rotation,
@@ -245,43 +502,64 @@ pub fn spawn_tile(
                 flip_y: tile.flip.1,
                 ..Default::default()
             },
-            texture_atlas: textures.mrmotext.clone(),
+            texture_atlas: atlas,
             transform: Transform {
                 translation: Vec3::new(
-                    grid_x(x, grid.x0, grid.w, zoom.0),
-                    grid_y(y, grid.y0, grid.h, zoom.0),
-                    util::z::GRID
+                    grid_x(x, grid.x0, grid.w, layout),
+                    grid_y(y, grid.y0, grid.h, layout),
+                    Grid::layer_z(layer)
                 ),
-                scale: Vec3::new(zoom.0, zoom.0, 1.0),
+                scale: Vec3::new(layout.scale, layout.scale, 1.0),
                 ..Default::default()
             },
+            visibility: if visible { Visibility::Inherited } else { Visibility::Hidden },
             ..Default::default()
         })
-        .insert(Clickable {
-            w: 8. * zoom.0,
-            h: 8. * zoom.0,
-            id: ButtonId::Grid(x, y),
-            hover_click: true,
-        })
+        .insert(Colored { bg: tile.bg, fg: tile.fg })
+        .insert(TilesetSprite)
         .insert(GridUI)
-        .insert(GridTile)
+        .insert(LayerTile)
         .id()
 }
 
 fn update_grid(
     mut update: EventReader<GridChanged>,
     grid: Res<Grid>,
-    mut tile: Query<&mut TextModeTextureAtlasSprite>,
+    mut tile: Query<(&mut TextModeTextureAtlasSprite, &mut Colored)>,
 ) {
     for GridChanged(vec) in update.iter() {
         for &(x, y) in vec.iter() {
-            let Some((t, e)) = grid.tiles.get(&(x as isize, y as isize)) else { continue };
-            let Ok(mut grid_tile) = tile.get_mut(*e) else { continue };
-            grid_tile.bg = t.bg.color();
-            grid_tile.fg = t.fg.color();
-            grid_tile.index = t.index;
-            grid_tile.rotation = t.rotation;
-            grid_tile.flip_x = t.flip.0;
+            for layer in grid.layers.iter() {
+                let (Some(t), Some(e)) = (layer.tiles.get(&(x, y)), layer.sprites.get(&(x, y))) else { continue };
+                let Ok((mut grid_tile, mut colored)) = tile.get_mut(*e) else { continue };
+                colored.bg = t.bg;
+                colored.fg = t.fg;
+                grid_tile.index = t.index;
+                grid_tile.rotation = t.rotation;
+                grid_tile.flip_x = t.flip.0;
+                grid_tile.flip_y = t.flip.1;
+            }
+        }
+    }
+}
+
+/// Applies layer order, visibility and alpha to their tiles.
+fn update_layers(
+    mut ev: EventReader<LayersChanged>,
+    grid: Option<Res<Grid>>,
+    mut tiles: Query<(&mut TextModeTextureAtlasSprite, &mut Transform, &mut Visibility), With<LayerTile>>,
+) {
+    let Some(grid) = grid else { return };
+    if ev.is_empty() { return; }
+    ev.clear();
+
+    for (i, layer) in grid.layers.iter().enumerate() {
+        let visibility = if layer.visible { Visibility::Inherited } else { Visibility::Hidden };
+        for e in layer.sprites.values() {
+            let Ok((mut sprite, mut transform, mut vis)) = tiles.get_mut(*e) else { continue };
+            sprite.alpha = layer.alpha;
+            transform.translation.z = Grid::layer_z(i);
+            vis.set_if_neq(visibility);
         }
     }
 }
@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::{AppState, util};
+use crate::grid::{Grid, GridChanged, GridResized, Tile};
+use crate::project::ProjectLoaded;
+use crate::ui::Prompt;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(History::default())
+            .add_system(undo_redo.in_set(OnUpdate(AppState::Editor)))
+            .add_system(
+                record
+                    .in_base_set(CoreSet::PostUpdate)
+                    .run_if(in_state(AppState::Editor))
+            )
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct Geometry {
+    x0: isize,
+    y0: isize,
+    w: usize,
+    h: usize,
+}
+
+impl Geometry {
+    fn of(grid: &Grid) -> Self {
+        Geometry { x0: grid.x0, y0: grid.y0, w: grid.w, h: grid.h }
+    }
+}
+
+/// Layer id and position.
+type Key = (u32, (isize, isize));
+
+/// Tiles and geometry before and after a group of changes.
+#[derive(Default)]
+struct Transaction {
+    geometry: Option<(Geometry, Geometry)>,
+    tiles: HashMap<Key, (Option<Tile>, Option<Tile>)>,
+}
+
+impl Transaction {
+    fn is_empty(&self) -> bool {
+        self.geometry.map_or(true, |(before, after)| before == after)
+            && self.tiles.values().all(|(before, after)| before == after)
+    }
+}
+
+/// Undo / redo stacks, fed by diffing the grid against a shadow copy
+/// every time a [GridChanged] or [GridResized] event is sent.
+#[derive(Resource, Default)]
+pub struct History {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    current: Option<Transaction>,
+    shadow: HashMap<Key, Tile>,
+    geometry: Option<Geometry>,
+    /// Adding, removing or reordering layers isn't undoable and forgets the history.
+    layers: Vec<u32>,
+}
+
+impl History {
+    /// Forgets every step, the next frame takes a new snapshot of the grid.
+    pub fn clear(&mut self) {
+        *self = History::default();
+    }
+
+    fn commit(&mut self) {
+        let Some(transaction) = self.current.take() else { return };
+        if transaction.is_empty() { return; }
+        self.undo.push_back(transaction);
+        if self.undo.len() > util::misc::HISTORY_SIZE { self.undo.pop_front(); }
+        self.redo.clear();
+    }
+}
+
+/// Missing tiles are respawned with the [Grid::blank] tile by [GridResized].
+fn same_tile(a: &Option<Tile>, b: &Option<Tile>, blank: &Tile) -> bool {
+    a.as_ref().unwrap_or(blank) == b.as_ref().unwrap_or(blank)
+}
+
+fn layer_ids(grid: &Grid) -> Vec<u32> {
+    grid.layers.iter().map(|l| l.id).collect()
+}
+
+fn record(
+    mut history: ResMut<History>,
+    mut changed: EventReader<GridChanged>,
+    mut resized: EventReader<GridResized>,
+    mut loaded: EventReader<ProjectLoaded>,
+    mouse: Res<Input<MouseButton>>,
+    grid: Option<Res<Grid>>,
+) {
+    let Some(grid) = grid else { return };
+
+    if !loaded.is_empty() || history.layers != layer_ids(&grid) {
+        loaded.clear();
+        history.clear();
+    }
+
+    let Some(geometry) = history.geometry else {
+        changed.clear();
+        resized.clear();
+        history.shadow = grid.layers.iter()
+            .flat_map(|l| l.tiles.iter().map(|(&pos, tile)| ((l.id, pos), tile.clone())))
+            .collect();
+        history.geometry = Some(Geometry::of(&grid));
+        history.layers = layer_ids(&grid);
+        return;
+    };
+
+    let mut positions = changed.iter()
+        .flat_map(|GridChanged(vec)| vec.iter().copied())
+        .flat_map(|pos| grid.layers.iter().map(move |l| (l.id, pos)))
+        .collect::<HashSet<Key>>();
+
+    let new_geometry = Geometry::of(&grid);
+    if !resized.is_empty() || geometry != new_geometry {
+        resized.clear();
+        positions.extend(history.shadow.keys().copied());
+        positions.extend(grid.layers.iter().flat_map(|l| l.tiles.keys().map(|pos| (l.id, *pos))));
+    }
+
+    let mut transaction = history.current.take().unwrap_or_default();
+    let mut dirty = false;
+
+    if geometry != new_geometry {
+        transaction.geometry = Some((transaction.geometry.map_or(geometry, |(before, _)| before), new_geometry));
+        history.geometry = Some(new_geometry);
+        dirty = true;
+    }
+
+    for key in positions {
+        let Some(layer) = grid.layer_index(key.0) else { continue };
+        let before = history.shadow.get(&key).cloned();
+        let after = grid.layers[layer].tiles.get(&key.1).cloned();
+        if before == after { continue }
+
+        match after {
+            Some(ref tile) => history.shadow.insert(key, tile.clone()),
+            None => history.shadow.remove(&key),
+        };
+        if same_tile(&before, &after, &grid.blank(layer)) && !transaction.tiles.contains_key(&key) { continue }
+
+        transaction.tiles.entry(key).or_insert((before, None)).1 = after;
+        dirty = true;
+    }
+
+    history.current = Some(transaction);
+
+    // A transaction lasts as long as the mouse is held and the grid keeps changing
+    if !dirty && !mouse.pressed(MouseButton::Left) {
+        history.commit();
+    }
+}
+
+fn undo_redo(
+    mut history: ResMut<History>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut grid_resized: EventWriter<GridResized>,
+    keys: Res<Input<KeyCode>>,
+    grid: Option<ResMut<Grid>>,
+    prompt: Option<Res<Prompt>>,
+) {
+    let Some(mut grid) = grid else { return };
+    if prompt.is_some() { return; }
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) { return; }
+
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    let undo = keys.just_pressed(KeyCode::Z) && !shift;
+    let redo = (keys.just_pressed(KeyCode::Z) && shift) || keys.just_pressed(KeyCode::Y);
+    if !undo && !redo { return; }
+
+    history.commit();
+    let transaction = if undo { history.undo.pop_back() } else { history.redo.pop() };
+    let Some(transaction) = transaction else { return };
+
+    // Geometry
+    if let Some((before, after)) = transaction.geometry {
+        let target = if undo { before } else { after };
+        grid.x0 = target.x0;
+        grid.y0 = target.y0;
+        grid.w = target.w;
+        grid.h = target.h;
+        history.geometry = Some(target);
+        grid_resized.send(GridResized);
+    }
+
+    // Tiles
+    let mut positions = Vec::new();
+    for (&key, (before, after)) in transaction.tiles.iter() {
+        let (id, pos) = key;
+        let Some(layer) = grid.layer_index(id) else { continue };
+        let target = if undo { before } else { after };
+        let target = match target {
+            None if grid.contains(pos) => Some(grid.blank(layer)),
+            _ => target.clone(),
+        };
+        match target {
+            Some(ref tile) => {
+                grid.layers[layer].tiles.insert(pos, tile.clone());
+                history.shadow.insert(key, tile.clone());
+            }
+            None => {
+                grid.layers[layer].tiles.remove(&pos);
+                history.shadow.remove(&key);
+            }
+        }
+        positions.push(pos);
+    }
+    grid_changed.send(GridChanged(positions));
+
+    if undo { history.redo.push(transaction); } else { history.undo.push_back(transaction); }
+}
+
+fn cleanup(
+    mut history: ResMut<History>,
+) {
+    history.clear();
+}
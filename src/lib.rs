@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use bevy_text_mode::TextModePlugin;
+
+use crate::grid::GridPlugin;
+use crate::history::HistoryPlugin;
+use crate::loading::LoadingPlugin;
+use crate::mouse::MousePlugin;
+use crate::palette::PalettePlugin;
+use crate::palette_editor::PaletteEditorPlugin;
+use crate::project::ProjectPlugin;
+use crate::quick_tiles::QuickTilesPlugin;
+use crate::screen::ScreenPlugin;
+use crate::shortcuts::ShortcutsPlugin;
+use crate::tilesets::TilesetsPlugin;
+use crate::toolbar::ToolbarPlugin;
+use crate::ui::UiPlugin;
+use crate::util::Palette;
+use crate::zoom::ZoomPlugin;
+
+mod ansi;
+pub mod convert;
+mod loading;
+mod toolbar;
+mod tools;
+mod util;
+mod mouse;
+mod quick_tiles;
+mod grid;
+mod history;
+mod palette;
+mod palette_editor;
+mod project;
+mod render;
+mod screen;
+mod shortcuts;
+mod tilesets;
+mod ui;
+mod zoom;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+enum AppState {
+    #[default]
+    Loading,
+    Editor,
+}
+
+/// Smallest area shown, bigger windows show more.
+const WIDTH: f32 = 8. * 40.;
+const HEIGHT: f32 = 8. * 25.;
+
+/// Opens the editor window.
+pub fn run() {
+    App::new()
+        .insert_resource(ClearColor(Palette::Black.color()))
+        .add_plugins(DefaultPlugins
+            .set(ImagePlugin::default_nearest())
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    resolution: (WIDTH * 4., HEIGHT * 4.).into(),
+                    title: "rtemo".to_string(),
+                    canvas: Some("#bevy".to_owned()),
+                    ..default()
+                }),
+                ..default()
+            })
+        )
+        .add_state::<AppState>()
+        .add_plugin(LoadingPlugin)
+        .add_plugin(GridPlugin)
+        .add_plugin(ToolbarPlugin)
+        .add_plugin(TextModePlugin)
+        .add_plugin(MousePlugin)
+        .add_plugin(QuickTilesPlugin)
+        .add_plugin(tools::ToolsPlugin)
+        .add_plugin(ProjectPlugin)
+        .add_plugin(UiPlugin)
+        .add_plugin(HistoryPlugin)
+        .add_plugin(ShortcutsPlugin)
+        .add_plugin(PalettePlugin)
+        .add_plugin(PaletteEditorPlugin)
+        .add_plugin(TilesetsPlugin)
+        .add_plugin(ZoomPlugin)
+        .add_plugin(ScreenPlugin)
+        .add_startup_system(init)
+        .run();
+}
+
+fn init(mut commands: Commands) {
+    commands.spawn(Camera2dBundle {
+        transform: Transform {
+            scale: Vec3::new(0.25, 0.25, 1.),
+            translation: Vec3::new(WIDTH / 2., HEIGHT / 2., 100.),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+}
\ No newline at end of file
@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 
 use crate::AppState;
+use crate::tilesets::{Tileset, Tilesets};
 
 pub struct LoadingPlugin;
 
@@ -12,20 +13,25 @@ impl Plugin for LoadingPlugin {
                 LoadingState::new(AppState::Loading)
                     .continue_to_state(AppState::Editor),
             )
-            .add_collection_to_loading_state::<_, Textures>(AppState::Loading);
+            .add_collection_to_loading_state::<_, Textures>(AppState::Loading)
+            .init_resource_after_loading_state::<_, Tilesets>(AppState::Loading);
     }
 }
 
 #[derive(AssetCollection, Resource)]
 pub struct Textures {
-    #[asset(texture_atlas(tile_size_x = 16., tile_size_y = 16., columns = 13, rows = 1, padding_x = 0., padding_y = 0.))]
+    #[asset(texture_atlas(tile_size_x = 16., tile_size_y = 16., columns = 23, rows = 1, padding_x = 0., padding_y = 0.))]
     #[asset(path = "icons.png")]
     pub icons: Handle<TextureAtlas>,
 
     #[asset(texture_atlas(tile_size_x = 8., tile_size_y = 8., columns = 32, rows = 32, padding_x = 0., padding_y = 0.))]
+    /// Font of the interface, drawings use [Tilesets].
     #[asset(path = "MRMOTEXT EX.png")]
     pub mrmotext: Handle<TextureAtlas>,
 
+    #[asset(path = "tilesets", collection(typed))]
+    pub tilesets: Vec<Handle<Tileset>>,
+
     #[asset(path = "slot.png")]
     pub slot: Handle<Image>,
 
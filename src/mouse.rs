@@ -1,8 +1,10 @@
 use bevy::prelude::*;
 
 use crate::AppState;
+use crate::palette::TileColor;
+use crate::screen::Cursor;
 use crate::tools::Tools;
-use crate::util::Palette;
+use crate::zoom::Panning;
 
 pub struct MousePlugin;
 
@@ -23,7 +25,9 @@ pub enum ButtonId {
     Tool(Tools),
     Grid(isize, isize),
     QuickTile(usize),
-    QuickColor(Palette),
+    QuickColor(TileColor),
+    /// Channel and segment of a palette editor slider.
+    ColorSlider(usize, usize),
     Custom(&'static str),
 }
 
@@ -51,7 +55,8 @@ fn update(
     mut ev: EventWriter<Clicked>,
     buttons: Query<(Entity, &Transform, &Clickable, Option<&AlreadyClicked>)>,
     mouse: Res<Input<MouseButton>>,
-    mut windows: Query<&mut Window>,
+    panning: Option<Res<Panning>>,
+    cursor: Res<Cursor>,
 ) {
     for (e, _, _, _) in buttons.iter() {
         commands.entity(e).remove::<Hover>();
@@ -61,12 +66,12 @@ fn update(
     let just_clicked_right = mouse.just_pressed(MouseButton::Right);
     let clicked_left = mouse.pressed(MouseButton::Left);
     let released = mouse.just_released(MouseButton::Left);
-    let window = windows.get_single().unwrap();
-    if let Some(pos) = window.cursor_position() {
+    if let Some(pos) = cursor.0 {
         for (e, t, c, already_clicked) in buttons.iter() {
+            if panning.is_some() && matches!(c.id, ButtonId::Grid(..)) { continue }
             let x = t.translation.x + c.w / 2.;
             let y = t.translation.y + c.h / 2.;
-            let hover = (pos.x / 4. - x).abs() <= c.w / 2. && (pos.y / 4. - y).abs() <= c.h / 2.;
+            let hover = (pos.x - x).abs() <= c.w / 2. && (pos.y - y).abs() <= c.h / 2.;
             let mut entity = commands.entity(e);
             if hover { entity.insert(Hover); }
             let do_click = hover && (just_clicked_left || just_clicked_right);
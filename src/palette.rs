@@ -0,0 +1,334 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_text_mode::TextModeTextureAtlasSprite;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{AppState, util};
+use crate::grid::{Grid, GridChanged};
+use crate::quick_tiles::{SelectColor, Selection};
+use crate::tools::TextCursorState;
+use crate::ui::{OpenPrompt, Prompt, PromptKind, PromptSubmitted, ShowMessage};
+use crate::util::Palette;
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(TilePalette::default())
+            .add_event::<LoadPalette>()
+            .add_systems((on_keys, on_prompt, load).chain().in_set(OnUpdate(AppState::Editor)))
+            .add_system(apply_colors.in_base_set(CoreSet::PostUpdate));
+    }
+}
+
+/// Color of a tile, an entry of the [TilePalette].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(from = "TileColorRepr")]
+pub enum TileColor {
+    Transparent,
+    Index(u8),
+}
+
+/// Drawings saved with the fixed palette use its color names.
+#[derive(Deserialize)]
+enum TileColorRepr {
+    Transparent,
+    Index(u8),
+    Black,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P,
+}
+
+impl From<TileColorRepr> for TileColor {
+    fn from(repr: TileColorRepr) -> Self {
+        match repr {
+            TileColorRepr::Transparent | TileColorRepr::Black => TileColor::Transparent,
+            TileColorRepr::Index(i) => TileColor::Index(i),
+            TileColorRepr::A => TileColor::Index(0),
+            TileColorRepr::B => TileColor::Index(1),
+            TileColorRepr::C => TileColor::Index(2),
+            TileColorRepr::D => TileColor::Index(3),
+            TileColorRepr::E => TileColor::Index(4),
+            TileColorRepr::F => TileColor::Index(5),
+            TileColorRepr::G => TileColor::Index(6),
+            TileColorRepr::H => TileColor::Index(7),
+            TileColorRepr::I => TileColor::Index(8),
+            TileColorRepr::J => TileColor::Index(9),
+            TileColorRepr::K => TileColor::Index(10),
+            TileColorRepr::L => TileColor::Index(11),
+            TileColorRepr::M => TileColor::Index(12),
+            TileColorRepr::N => TileColor::Index(13),
+            TileColorRepr::O => TileColor::Index(14),
+            TileColorRepr::P => TileColor::Index(15),
+        }
+    }
+}
+
+impl From<Palette> for TileColor {
+    fn from(p: Palette) -> Self {
+        match p {
+            Palette::Transparent | Palette::Black => TileColor::Transparent,
+            p => TileColor::Index(Palette::iter().position(|q| q == p).unwrap_or(0) as u8),
+        }
+    }
+}
+
+/// Colors of the drawing, 2 to 256 entries.
+#[derive(Resource, Clone)]
+pub struct TilePalette {
+    pub name: String,
+    pub colors: Vec<Color>,
+}
+
+impl Default for TilePalette {
+    /// The 16 colors of [Palette].
+    fn default() -> Self {
+        TilePalette {
+            name: "rtemo".to_string(),
+            colors: Palette::iter()
+                .filter(|p| *p != Palette::Transparent && *p != Palette::Black)
+                .map(|p| p.color())
+                .collect(),
+        }
+    }
+}
+
+impl TilePalette {
+    pub const MIN: usize = 2;
+    pub const MAX: usize = 256;
+
+    pub fn color(&self, color: TileColor) -> Color {
+        match color {
+            TileColor::Index(i) => self.colors.get(i as usize).copied().unwrap_or(Color::NONE),
+            TileColor::Transparent => Color::NONE,
+        }
+    }
+
+    fn new(name: &str, colors: Vec<Color>) -> Result<Self, String> {
+        if colors.len() < TilePalette::MIN || colors.len() > TilePalette::MAX {
+            return Err(format!("{} colors, a palette needs {} to {}", colors.len(), TilePalette::MIN, TilePalette::MAX));
+        }
+        Ok(TilePalette { name: name.to_string(), colors })
+    }
+
+    /// Reads GIMP `.gpl` palettes, or any list of hex colors like Lospec `.hex` and
+    /// Paint.NET `.txt` files. 8 digit colors are `AARRGGBB`, or `#RRGGBBAA` with a `#`.
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        if text.trim_start().starts_with("GIMP Palette") { return TilePalette::parse_gpl(name, text); }
+
+        let mut colors = vec![];
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with(';') || line.starts_with("//") { continue }
+
+            // Words right after a `#` start the parts following the first one
+            for (i, part) in line.split('#').enumerate() {
+                for (j, word) in part.split(|c: char| !c.is_ascii_alphanumeric()).enumerate() {
+                    if !word.chars().all(|c| c.is_ascii_hexdigit()) { continue }
+                    let hex = match (word.len(), i > 0 && j == 0) {
+                        (6, _) | (8, true) => word.to_string(),
+                        (8, false) => format!("{}{}", &word[2..], &word[..2]),
+                        _ => continue,
+                    };
+                    colors.push(Color::hex(hex).map_err(|e| format!("{}: {:?}", word, e))?);
+                }
+            }
+        }
+
+        TilePalette::new(name, colors)
+    }
+
+    fn parse_gpl(name: &str, text: &str) -> Result<Self, String> {
+        let mut name = name.to_string();
+        let mut colors = vec![];
+        for (n, line) in text.lines().enumerate().skip(1) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") { continue }
+            if let Some(n) = line.strip_prefix("Name:") {
+                name = n.trim().to_string();
+                continue;
+            }
+
+            let rgb = line.split_whitespace()
+                .take(3)
+                .map(|c| c.parse::<u8>())
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| format!("line {}: expected `r g b`", n + 1))?;
+            let [r, g, b] = rgb[..] else { return Err(format!("line {}: expected `r g b`", n + 1)) };
+            colors.push(Color::rgb_u8(r, g, b));
+        }
+
+        TilePalette::new(&name, colors)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        TilePalette::parse(&name, &text)
+    }
+
+    /// Palette saved in a project, fully transparent entries at the end are dropped.
+    pub fn from_hex(name: &str, hex: &[String]) -> Result<Self, String> {
+        let mut colors = hex.iter()
+            .map(|h| Color::hex(h.trim_start_matches('#')).map_err(|e| format!("{}: {:?}", h, e)))
+            .collect::<Result<Vec<Color>, String>>()?;
+        while colors.last().map_or(false, |c| c.a() == 0.) { colors.pop(); }
+        TilePalette::new(name, colors)
+    }
+
+    pub fn to_hex(&self) -> Vec<String> {
+        self.colors.iter().map(|c| util::color_to_hex(*c)).collect()
+    }
+
+    pub fn nearest(&self, color: Color) -> TileColor {
+        if color.a() == 0. { return TileColor::Transparent; }
+        let distance = |c: &Color| (c.r() - color.r()).powi(2) + (c.g() - color.g()).powi(2) + (c.b() - color.b()).powi(2);
+        self.colors.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .map_or(TileColor::Transparent, |(i, _)| TileColor::Index(i as u8))
+    }
+
+    /// Number of colors in the tuple format: the palette, then transparent
+    /// and black from the original palette which are both transparent.
+    pub fn tuple_count(&self) -> usize {
+        self.colors.len() + 2
+    }
+
+    pub fn to_tuple(&self, color: TileColor) -> usize {
+        match color {
+            TileColor::Index(i) => i as usize,
+            TileColor::Transparent => self.colors.len(),
+        }
+    }
+
+    pub fn from_tuple(&self, index: usize) -> TileColor {
+        if index < self.colors.len() { TileColor::Index(index as u8) } else { TileColor::Transparent }
+    }
+}
+
+/// Colors of a text mode sprite, resolved with the [TilePalette].
+#[derive(Component)]
+pub struct Colored {
+    pub bg: TileColor,
+    pub fg: TileColor,
+}
+
+fn apply_colors(
+    palette: Res<TilePalette>,
+    mut sprites: Query<(Ref<Colored>, &mut TextModeTextureAtlasSprite)>,
+) {
+    for (colored, mut sprite) in sprites.iter_mut() {
+        if !palette.is_changed() && !colored.is_changed() { continue }
+        sprite.bg = palette.color(colored.bg);
+        sprite.fg = palette.color(colored.fg);
+    }
+}
+
+/// Replaces the palette, tiles take the nearest color of the new one.
+pub struct LoadPalette(pub PathBuf);
+
+/// Ctrl + P loads a palette.
+fn on_keys(
+    keys: Res<Input<KeyCode>>,
+    prompt: Option<Res<Prompt>>,
+    cursor: Option<Res<TextCursorState>>,
+    mut open_prompt: EventWriter<OpenPrompt>,
+) {
+    if prompt.is_some() || cursor.is_some() { return; }
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) || !keys.just_pressed(KeyCode::P) { return; }
+
+    open_prompt.send(OpenPrompt {
+        kind: PromptKind::LoadPalette,
+        label: "Palette:".to_string(),
+        default: util::misc::DEFAULT_PALETTE.to_string(),
+    });
+}
+
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut load: EventWriter<LoadPalette>,
+) {
+    for PromptSubmitted(kind, path) in submitted.iter() {
+        if *kind != PromptKind::LoadPalette || path.is_empty() { continue }
+        load.send(LoadPalette(PathBuf::from(path)));
+    }
+}
+
+fn load(
+    mut ev: EventReader<LoadPalette>,
+    mut palette: ResMut<TilePalette>,
+    mut selection: ResMut<Selection>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut select_color: EventWriter<SelectColor>,
+    mut message: EventWriter<ShowMessage>,
+    grid: Option<ResMut<Grid>>,
+) {
+    let Some(mut grid) = grid else { return };
+    for LoadPalette(path) in ev.iter() {
+        let new = match TilePalette::load(path) {
+            Ok(new) => new,
+            Err(e) => {
+                message.send(ShowMessage(format!("Couldn't load palette: {}", e)));
+                continue;
+            }
+        };
+
+        let remap = |color: &mut TileColor| *color = new.nearest(palette.color(*color));
+        let mut positions = vec![];
+        for layer in grid.layers.iter_mut() {
+            for (pos, tile) in layer.tiles.iter_mut() {
+                remap(&mut tile.bg);
+                remap(&mut tile.fg);
+                positions.push(*pos);
+            }
+        }
+        remap(&mut selection.bg);
+        remap(&mut selection.fg);
+
+        message.send(ShowMessage(format!("Palette {}, {} colors", new.name, new.colors.len())));
+        *palette = new;
+        grid_changed.send(GridChanged(positions));
+        select_color.send(SelectColor(selection.fg, false));
+        select_color.send(SelectColor(selection.bg, true));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_lists() {
+        let palette = TilePalette::parse("test", "; Paint.NET\nFFFF0000\n80 00ff00\n#0000ff80, 123 #abc").unwrap();
+        assert_eq!(palette.colors, vec![
+            Color::rgba_u8(255, 0, 0, 255),
+            Color::rgba_u8(0, 255, 0, 255),
+            Color::rgba_u8(0, 0, 255, 128),
+        ]);
+        assert_eq!(TilePalette::parse("test", "// #ffffff\n000000 ffffff").unwrap().colors.len(), 2);
+    }
+
+    #[test]
+    fn gimp_palettes() {
+        let palette = TilePalette::parse("file", "GIMP Palette\nName: Test\nColumns: 2\n# comment\n  0   0   0 Black\n255 255 255\tWhite").unwrap();
+        assert_eq!(palette.name, "Test");
+        assert_eq!(palette.colors, vec![Color::rgb_u8(0, 0, 0), Color::rgb_u8(255, 255, 255)]);
+    }
+
+    fn error(text: &str) -> String {
+        TilePalette::parse("test", text).err().unwrap()
+    }
+
+    #[test]
+    fn bad_input() {
+        assert_eq!(error(""), "0 colors, a palette needs 2 to 256");
+        assert_eq!(error("#fff #12345 g00000 ffffff"), "1 colors, a palette needs 2 to 256");
+        assert_eq!(error(&"000000\n".repeat(257)), "257 colors, a palette needs 2 to 256");
+        assert_eq!(error("GIMP Palette\n0 0 0\n255 255"), "line 3: expected `r g b`");
+        assert_eq!(error("GIMP Palette\n0 0 256"), "line 2: expected `r g b`");
+    }
+}
@@ -0,0 +1,321 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::{AppState, util};
+use crate::grid::{Grid, GridChanged};
+use crate::loading::Textures;
+use crate::mouse::{ButtonId, Clickable, Clicked};
+use crate::palette::{TileColor, TilePalette};
+use crate::ui::{self, OpenPrompt, PromptKind, PromptSubmitted, ShowMessage};
+use crate::util::Palette;
+
+pub struct PaletteEditorPlugin;
+
+impl Plugin for PaletteEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems((on_click, on_prompt, update_panel).chain().in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+#[derive(Component)]
+struct PaletteEditorUI;
+
+/// Texts depending on the edited color, respawned when it changes.
+#[derive(Component)]
+struct ValuesText;
+
+#[derive(Component)]
+struct Segment {
+    channel: usize,
+    n: usize,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Operation {
+    Swap,
+    Replace,
+}
+
+/// Palette entry being edited, its panel covers the quick tiles while it exists.
+#[derive(Resource)]
+pub struct PaletteEditor {
+    entry: usize,
+    /// Waiting for a click on the other color.
+    pending: Option<Operation>,
+}
+
+const CHANNELS: [char; 6] = ['R', 'G', 'B', 'H', 'S', 'V'];
+const SEGMENTS: usize = 16;
+const X0: f32 = 56.;
+
+/// Red, green, blue, hue, saturation and value, all in 0..1.
+fn channels(color: Color) -> [f32; 6] {
+    let [r, g, b, _] = color.as_rgba_f32();
+    let max = r.max(g).max(b);
+    let d = max - r.min(g).min(b);
+    let h = if d == 0. { 0. }
+        else if max == r { ((g - b) / d).rem_euclid(6.) }
+        else if max == g { (b - r) / d + 2. }
+        else { (r - g) / d + 4. };
+    let s = if max == 0. { 0. } else { d / max };
+    [r, g, b, h / 6., s, max]
+}
+
+fn hsv(h: f32, s: f32, v: f32) -> Color {
+    let h = (h * 6.).rem_euclid(6.);
+    let c = v * s;
+    let x = c * (1. - (h % 2. - 1.).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    let m = v - c;
+    Color::rgb(r + m, g + m, b + m)
+}
+
+fn with_channel(color: Color, channel: usize, value: f32) -> Color {
+    let mut c = channels(color);
+    c[channel] = value;
+    let new = if channel < 3 { Color::rgb(c[0], c[1], c[2]) } else { hsv(c[3], c[4], c[5]) };
+    new.with_a(color.a())
+}
+
+fn segment_value(n: usize) -> f32 {
+    n as f32 / (SEGMENTS - 1) as f32
+}
+
+/// Bottom left corner of a slider, RGB on the left, HSV on the right.
+fn slider_position(channel: usize) -> Vec2 {
+    Vec2::new(X0 + 4. + 128. * (channel / 3) as f32, 24. - 8. * (channel % 3) as f32)
+}
+
+/// Segments show the color they would set, the current one is taller.
+fn segment_sprite(color: Color, channel: usize, n: usize) -> Sprite {
+    let current = (channels(color)[channel] * (SEGMENTS - 1) as f32).round() as usize == n;
+    Sprite {
+        color: with_channel(color, channel, segment_value(n)).with_a(1.),
+        custom_size: Some(Vec2::new(4., if current { 8. } else { 4. })),
+        anchor: if current { Anchor::BottomLeft } else { Anchor::Custom(Vec2::new(-0.5, -1.)) },
+        ..Default::default()
+    }
+}
+
+fn spawn_panel(commands: &mut Commands, textures: &Res<Textures>, color: Color) {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: Palette::E.color(),
+                custom_size: Some(Vec2::new(256., 40.)),
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(X0, 0., util::z::PALETTE_EDITOR_BG),
+            ..Default::default()
+        })
+        .insert(PaletteEditorUI);
+
+    for (channel, label) in CHANNELS.iter().enumerate() {
+        let position = slider_position(channel);
+        let e = ui::spawn_text(commands, textures, &label.to_string(), position.extend(util::z::TEXT), Palette::A.color(), Palette::E.color());
+        commands.entity(e).insert(PaletteEditorUI);
+
+        for n in 0..SEGMENTS {
+            commands
+                .spawn(SpriteBundle {
+                    sprite: segment_sprite(color, channel, n),
+                    transform: Transform::from_xyz(position.x + 12. + 4. * n as f32, position.y, util::z::PALETTE_EDITOR),
+                    ..Default::default()
+                })
+                .insert(Clickable {
+                    w: 4.,
+                    h: 8.,
+                    id: ButtonId::ColorSlider(channel, n),
+                    hover_click: true,
+                })
+                .insert(Segment { channel, n })
+                .insert(PaletteEditorUI);
+        }
+    }
+
+    for (x, label, id) in [(136., "Hex", "hex"), (168., "Swap", "swap"), (208., "Repl", "replace"), (244., "x", "close")] {
+        let e = ui::spawn_text(commands, textures, label, Vec3::new(X0 + x, 32., util::z::TEXT), Palette::A.color(), Palette::D.color());
+        commands.entity(e)
+            .insert(Clickable {
+                w: 8. * label.len() as f32,
+                h: 8.,
+                id: ButtonId::Custom(id),
+                hover_click: false,
+            })
+            .insert(PaletteEditorUI);
+    }
+}
+
+fn spawn_values(commands: &mut Commands, textures: &Res<Textures>, editor: &PaletteEditor, color: Color) {
+    let mut texts = vec![];
+
+    let hex = util::color_to_hex(color);
+    texts.push((Vec2::new(X0 + 4., 32.), format!("Color {} {}", editor.entry, &hex[..7])));
+
+    let c = channels(color);
+    let values = [c[0] * 255., c[1] * 255., c[2] * 255., c[3] * 360., c[4] * 100., c[5] * 100.];
+    for (channel, value) in values.iter().enumerate() {
+        texts.push((slider_position(channel) + Vec2::new(80., 0.), format!("{:>3}", value.round() as u32)));
+    }
+
+    texts.push((Vec2::new(X0 + 4., 0.), match editor.pending {
+        Some(Operation::Swap) => "Click the color to swap with",
+        Some(Operation::Replace) => "Click the replacement color",
+        None => "Shift+click: edit another color",
+    }.to_string()));
+
+    for (position, text) in texts {
+        let e = ui::spawn_text(commands, textures, &text, position.extend(util::z::TEXT), Palette::A.color(), Palette::E.color());
+        commands.entity(e).insert(ValuesText).insert(PaletteEditorUI);
+    }
+}
+
+/// Shift + click on a swatch edits it, the panel's buttons:
+/// - sliders set a channel, they can be dragged
+/// - `Hex` types the color, `x` closes the panel
+/// - `Swap` exchanges the color with the next one clicked, everywhere in the drawing
+/// - `Repl` paints the next color clicked over every use of the edited one
+fn on_click(
+    mut commands: Commands,
+    mut clicked: EventReader<Clicked>,
+    mut open_prompt: EventWriter<OpenPrompt>,
+    mut message: EventWriter<ShowMessage>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut palette: ResMut<TilePalette>,
+    mut editor: Option<ResMut<PaletteEditor>>,
+    grid: Option<ResMut<Grid>>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let Some(mut grid) = grid else { return };
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    for Clicked(id, _) in clicked.iter() {
+        match (id, editor.as_mut()) {
+            (ButtonId::QuickColor(TileColor::Transparent), _) if shift => {
+                message.send(ShowMessage("Transparent can't be edited".to_string()));
+            }
+            (ButtonId::QuickColor(TileColor::Index(i)), Some(editor)) if shift => {
+                editor.entry = *i as usize;
+                editor.pending = None;
+            }
+            (ButtonId::QuickColor(TileColor::Index(i)), None) if shift => {
+                commands.insert_resource(PaletteEditor { entry: *i as usize, pending: None });
+            }
+            (ButtonId::QuickColor(color), Some(editor)) => {
+                let Some(operation) = editor.pending.take() else { continue };
+                let entry = editor.entry;
+                match (operation, color) {
+                    (Operation::Swap, TileColor::Index(i)) => {
+                        palette.colors.swap(entry, *i as usize);
+                        message.send(ShowMessage(format!("Swapped colors {} and {}", entry, i)));
+                    }
+                    (Operation::Swap, TileColor::Transparent) => {
+                        message.send(ShowMessage("Transparent can't be swapped".to_string()));
+                    }
+                    (Operation::Replace, _) => {
+                        let from = TileColor::Index(entry as u8);
+                        let mut positions = vec![];
+                        for layer in grid.layers.iter_mut() {
+                            for (pos, tile) in layer.tiles.iter_mut() {
+                                if tile.bg != from && tile.fg != from { continue }
+                                if tile.bg == from { tile.bg = *color; }
+                                if tile.fg == from { tile.fg = *color; }
+                                positions.push(*pos);
+                            }
+                        }
+                        message.send(ShowMessage(format!("Replaced color {} on {} tiles", entry, positions.len())));
+                        grid_changed.send(GridChanged(positions));
+                    }
+                }
+            }
+            (ButtonId::ColorSlider(channel, n), Some(editor)) => {
+                let color = palette.colors[editor.entry];
+                let new = with_channel(color, *channel, segment_value(*n));
+                // Dragging over the same segment keeps clicking it
+                if new != color { palette.colors[editor.entry] = new; }
+            }
+            (ButtonId::Custom("hex"), Some(editor)) => {
+                open_prompt.send(OpenPrompt {
+                    kind: PromptKind::EditColor,
+                    label: "Color:".to_string(),
+                    default: util::color_to_hex(palette.colors[editor.entry])[..7].to_string(),
+                });
+            }
+            (ButtonId::Custom("swap"), Some(editor)) => editor.pending = Some(Operation::Swap),
+            (ButtonId::Custom("replace"), Some(editor)) => editor.pending = Some(Operation::Replace),
+            (ButtonId::Custom("close"), Some(_)) => commands.remove_resource::<PaletteEditor>(),
+            _ => (),
+        }
+    }
+}
+
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut message: EventWriter<ShowMessage>,
+    mut palette: ResMut<TilePalette>,
+    editor: Option<Res<PaletteEditor>>,
+) {
+    let Some(editor) = editor else { return };
+    for PromptSubmitted(kind, hex) in submitted.iter() {
+        if *kind != PromptKind::EditColor || hex.is_empty() { continue }
+        match Color::hex(hex.trim_start_matches('#')) {
+            Ok(color) => palette.colors[editor.entry] = color,
+            Err(_) => message.send(ShowMessage(format!("Not a color: {}", hex))),
+        }
+    }
+}
+
+fn update_panel(
+    mut commands: Commands,
+    editor: Option<Res<PaletteEditor>>,
+    palette: Res<TilePalette>,
+    textures: Res<Textures>,
+    panel: Query<Entity, With<PaletteEditorUI>>,
+    values: Query<Entity, With<ValuesText>>,
+    mut segments: Query<(&Segment, &mut Sprite)>,
+) {
+    let Some(editor) = editor else {
+        panel.iter().for_each(|e| commands.entity(e).despawn_recursive());
+        return;
+    };
+
+    // Loading a smaller palette
+    let Some(&color) = palette.colors.get(editor.entry) else {
+        commands.remove_resource::<PaletteEditor>();
+        return;
+    };
+
+    if editor.is_added() {
+        panel.iter().for_each(|e| commands.entity(e).despawn_recursive());
+        spawn_panel(&mut commands, &textures, color);
+    } else if !editor.is_changed() && !palette.is_changed() {
+        return;
+    }
+
+    values.iter().for_each(|e| commands.entity(e).despawn_recursive());
+    spawn_values(&mut commands, &textures, &editor, color);
+
+    for (segment, mut sprite) in segments.iter_mut() {
+        *sprite = segment_sprite(color, segment.channel, segment.n);
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<PaletteEditorUI>>,
+) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    commands.remove_resource::<PaletteEditor>();
+}
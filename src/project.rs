@@ -0,0 +1,321 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, util};
+use crate::grid::{Grid, GridResized, LayersChanged, Tile};
+use crate::palette::TilePalette;
+use crate::quick_tiles::{SelectColor, Selection, SelectTile};
+use crate::tilesets::{SwitchTileset, Tileset, Tilesets};
+use crate::ui::ShowMessage;
+
+pub struct ProjectPlugin;
+
+impl Plugin for ProjectPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<SaveProject>()
+            .add_event::<OpenProject>()
+            .add_event::<ProjectLoaded>()
+            .add_event::<NewProject>()
+            .insert_resource(Document { path: None })
+            .add_systems((save, open, create).in_set(OnUpdate(AppState::Editor)));
+    }
+}
+
+/// The file the current drawing was opened from or last saved to.
+#[derive(Resource)]
+pub struct Document {
+    pub path: Option<PathBuf>,
+}
+
+pub struct SaveProject(pub PathBuf);
+
+pub struct OpenProject(pub PathBuf);
+
+pub struct ProjectLoaded;
+
+/// Starts an empty drawing of this size, keeping the tileset and palette.
+pub struct NewProject(pub usize, pub usize);
+
+/// Content of a `.rtemo` file.
+#[derive(Serialize, Deserialize)]
+pub struct Project {
+    pub tileset: String,
+    #[serde(default)]
+    pub palette_name: String,
+    pub palette: Vec<String>,
+    pub selection: Selection,
+    pub grid: ProjectGrid,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProjectGrid {
+    pub x0: isize,
+    pub y0: isize,
+    pub w: usize,
+    pub h: usize,
+    /// Bottom layer first.
+    #[serde(default)]
+    pub layers: Vec<ProjectLayer>,
+    /// Single layer of drawings saved before layers.
+    #[serde(default, skip_serializing)]
+    tiles: Vec<((isize, isize), Tile)>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProjectLayer {
+    pub name: String,
+    pub visible: bool,
+    pub alpha: f32,
+    pub tiles: Vec<((isize, isize), Tile)>,
+}
+
+impl ProjectLayer {
+    pub fn new(name: &str, mut tiles: Vec<((isize, isize), Tile)>) -> Self {
+        tiles.sort_by_key(|((x, y), _)| (*y, *x));
+        ProjectLayer { name: name.to_string(), visible: true, alpha: 1., tiles }
+    }
+}
+
+impl ProjectGrid {
+    pub fn new(x0: isize, y0: isize, w: usize, h: usize, layers: Vec<ProjectLayer>) -> Self {
+        ProjectGrid { x0, y0, w, h, layers, tiles: vec![] }
+    }
+
+    pub fn from_grid(grid: &Grid) -> Self {
+        let layers = grid.layers.iter()
+            .map(|layer| ProjectLayer {
+                visible: layer.visible,
+                alpha: layer.alpha,
+                ..ProjectLayer::new(&layer.name, layer.tiles.iter().map(|(&pos, tile)| (pos, tile.clone())).collect())
+            })
+            .collect();
+
+        ProjectGrid::new(grid.x0, grid.y0, grid.w, grid.h, layers)
+    }
+
+    /// Visible layers merged into one, ignoring their alpha.
+    pub fn flatten(&self) -> Vec<((isize, isize), Tile)> {
+        let mut tiles: HashMap<(isize, isize), Tile> = HashMap::new();
+        for layer in self.layers.iter().filter(|l| l.visible) {
+            for (pos, tile) in layer.tiles.iter() {
+                let merged = match tiles.get(pos) {
+                    Some(below) => tile.over(below),
+                    None => tile.clone(),
+                };
+                tiles.insert(*pos, merged);
+            }
+        }
+
+        let mut tiles = tiles.into_iter().collect::<Vec<((isize, isize), Tile)>>();
+        tiles.sort_by_key(|((x, y), _)| (*y, *x));
+        tiles
+    }
+}
+
+impl Project {
+    pub fn new(grid: ProjectGrid, selection: Selection, palette: &TilePalette, tileset: &Tileset) -> Self {
+        Project {
+            tileset: tileset.name.clone(),
+            palette_name: palette.name.clone(),
+            palette: palette.to_hex(),
+            selection,
+            grid,
+        }
+    }
+
+    /// Drawings saved before custom palettes list the 16 default colors then 2 transparent ones.
+    pub fn tile_palette(&self) -> Result<TilePalette, String> {
+        let name = if self.palette_name.is_empty() { TilePalette::default().name } else { self.palette_name.clone() };
+        TilePalette::from_hex(&name, &self.palette)
+    }
+
+    pub fn parse(text: &str) -> Result<Project, String> {
+        let mut project = ron::from_str::<Project>(text).map_err(|e| e.to_string())?;
+        let grid = &mut project.grid;
+        if grid.layers.is_empty() {
+            grid.layers.push(ProjectLayer::new("Background", std::mem::take(&mut grid.tiles)));
+        }
+
+        // Same limits as the resize and layers tools
+        let max = util::size::MAX_GRID;
+        if !(1..=max).contains(&grid.w) || !(1..=max).contains(&grid.h) {
+            return Err(format!("{}x{} grid, sizes go from 1 to {}", grid.w, grid.h, max));
+        }
+        if grid.layers.len() > util::misc::MAX_LAYERS {
+            return Err(format!("{} layers, the most is {}", grid.layers.len(), util::misc::MAX_LAYERS));
+        }
+        Ok(project)
+    }
+
+    pub fn serialize(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|e| e.to_string())
+    }
+}
+
+/// Appends the project extension to paths without one.
+pub fn with_extension(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.extension().is_some() { return path; }
+    path.with_extension(util::misc::PROJECT_EXTENSION)
+}
+
+pub fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn save(
+    mut ev: EventReader<SaveProject>,
+    mut message: EventWriter<ShowMessage>,
+    mut document: ResMut<Document>,
+    grid: Option<Res<Grid>>,
+    selection: Res<Selection>,
+    palette: Res<TilePalette>,
+    tilesets: Res<Tilesets>,
+) {
+    let Some(grid) = grid else { return };
+    for SaveProject(path) in ev.iter() {
+        let project = Project::new(ProjectGrid::from_grid(&grid), selection.clone(), &palette, tilesets.active());
+
+        let result = project.serialize()
+            .and_then(|text| fs::write(path, text).map_err(|e| e.to_string()));
+        match result {
+            Ok(_) => {
+                document.path = Some(path.clone());
+                message.send(ShowMessage(format!("Saved {}", file_name(path))));
+            }
+            Err(e) => message.send(ShowMessage(format!("Couldn't save: {}", e))),
+        }
+    }
+}
+
+fn open(
+    mut commands: Commands,
+    mut ev: EventReader<OpenProject>,
+    mut message: EventWriter<ShowMessage>,
+    mut loaded: EventWriter<ProjectLoaded>,
+    mut grid_resized: EventWriter<GridResized>,
+    mut layers_changed: EventWriter<LayersChanged>,
+    mut select_tile: EventWriter<SelectTile>,
+    mut select_color: EventWriter<SelectColor>,
+    mut switch_tileset: EventWriter<SwitchTileset>,
+    mut document: ResMut<Document>,
+    mut selection: ResMut<Selection>,
+    mut palette: ResMut<TilePalette>,
+    grid: Option<ResMut<Grid>>,
+    tilesets: Res<Tilesets>,
+) {
+    let Some(mut grid) = grid else { return };
+    for OpenProject(path) in ev.iter() {
+        let project = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Project::parse(&text));
+        let project = match project {
+            Ok(project) => project,
+            Err(e) => {
+                message.send(ShowMessage(format!("Couldn't open {}: {}", file_name(path), e)));
+                continue;
+            }
+        };
+
+        match tilesets.find(&project.tileset) {
+            Some(i) => switch_tileset.send(SwitchTileset(i)),
+            None => message.send(ShowMessage(format!("Tileset {} not found", project.tileset))),
+        }
+
+        *palette = project.tile_palette().unwrap_or_else(|e| {
+            warn!("{} has an invalid palette: {}", file_name(path), e);
+            TilePalette::default()
+        });
+
+        // Replace grid
+        grid.clear_layers(&mut commands);
+        grid.x0 = project.grid.x0;
+        grid.y0 = project.grid.y0;
+        grid.w = project.grid.w;
+        grid.h = project.grid.h;
+
+        for project_layer in project.grid.layers {
+            let mut layer = grid.new_layer(&project_layer.name);
+            layer.visible = project_layer.visible;
+            layer.alpha = project_layer.alpha;
+            grid.layers.push(layer);
+
+            let i = grid.layers.len() - 1;
+            grid.layers[i].tiles.extend(project_layer.tiles);
+        }
+        grid_resized.send(GridResized);
+        layers_changed.send(LayersChanged);
+
+        // Restore selection
+        *selection = project.selection;
+        select_tile.send(SelectTile(selection.index));
+        select_color.send(SelectColor(selection.fg, false));
+        select_color.send(SelectColor(selection.bg, true));
+
+        document.path = Some(path.clone());
+        loaded.send(ProjectLoaded);
+        message.send(ShowMessage(format!("Opened {}", file_name(path))));
+    }
+}
+
+fn create(
+    mut commands: Commands,
+    mut ev: EventReader<NewProject>,
+    mut message: EventWriter<ShowMessage>,
+    mut loaded: EventWriter<ProjectLoaded>,
+    mut grid_resized: EventWriter<GridResized>,
+    mut layers_changed: EventWriter<LayersChanged>,
+    mut document: ResMut<Document>,
+    grid: Option<ResMut<Grid>>,
+) {
+    let Some(mut grid) = grid else { return };
+    for NewProject(w, h) in ev.iter() {
+        grid.clear_layers(&mut commands);
+        grid.x0 = 0;
+        grid.y0 = 0;
+        grid.w = *w;
+        grid.h = *h;
+        let layer = grid.new_layer("Background");
+        grid.layers.push(layer);
+        grid_resized.send(GridResized);
+        layers_changed.send(LayersChanged);
+
+        document.path = None;
+        loaded.send(ProjectLoaded);
+        message.send(ShowMessage(format!("New drawing {}x{}", w, h)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(w: usize, h: usize, layers: usize) -> Result<Project, String> {
+        let layers = (0..layers).map(|i| ProjectLayer::new(&format!("Layer {}", i), vec![])).collect();
+        let grid = ProjectGrid::new(-2, 3, w, h, layers);
+        let text = Project::new(grid, Selection::default(), &TilePalette::default(), &Tileset::default()).serialize()?;
+        Project::parse(&text)
+    }
+
+    #[test]
+    fn grid_limits() {
+        let max = util::size::MAX_GRID;
+        assert!(parse(max, 1, 1).is_ok());
+        assert_eq!(parse(0, 10, 1).err().unwrap(), format!("0x10 grid, sizes go from 1 to {}", max));
+        assert_eq!(parse(100000, 100000, 1).err().unwrap(), format!("100000x100000 grid, sizes go from 1 to {}", max));
+    }
+
+    #[test]
+    fn layer_limit() {
+        let max = util::misc::MAX_LAYERS;
+        assert_eq!(parse(4, 4, 0).unwrap().grid.layers.len(), 1);
+        assert!(parse(4, 4, max).is_ok());
+        assert_eq!(parse(4, 4, max + 1).err().unwrap(), format!("{} layers, the most is {}", max + 1, max));
+    }
+}
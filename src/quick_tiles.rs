@@ -3,11 +3,14 @@ use std::slice::SliceIndex;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 use bevy_text_mode::{TextModeSpriteSheetBundle, TextModeTextureAtlasSprite};
-use strum::IntoEnumIterator;
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::{AppState, util};
 use crate::loading::Textures;
 use crate::mouse::{ButtonId, Clickable, Clicked, Hover};
+use crate::palette::{Colored, TileColor, TilePalette};
+use crate::palette_editor::PaletteEditor;
+use crate::tilesets::{Tilesets, TilesetSprite};
 use crate::toolbar::SelectedTool;
 use crate::tools::Tools;
 use crate::util::Palette;
@@ -20,18 +23,14 @@ impl Plugin for QuickTilesPlugin {
             .add_event::<SelectTile>()
             .add_event::<SelectColor>()
             .add_event::<QuickTilesChanged>()
-            .insert_resource(Selection {
-                index: util::misc::DEFAULT_TILE,
-                bg: Palette::E,
-                fg: Palette::A,
-                flip: false,
-                rotation: 0,
-            })
+            .insert_resource(Selection::default())
+            .insert_resource(PalettePage(0))
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
             .add_systems(
                 (update, on_click,
                  update_tiles_index, update_active_tile,
-                 update_colors, update_range)
+                 update_colors, update_range,
+                 update_palette_page, update_swatches)
                     .in_set(OnUpdate(AppState::Editor))
             )
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
@@ -44,8 +43,13 @@ struct QuickTilesUI;
 #[derive(Component)]
 struct QuickTile;
 
+/// Slot of the palette panel.
 #[derive(Component)]
-struct ColorButton;
+struct ColorButton(usize);
+
+/// Page of the palette panel, for palettes that don't fit in it.
+#[derive(Resource)]
+struct PalettePage(usize);
 
 #[derive(Component)]
 struct QuickTileId {
@@ -63,83 +67,80 @@ struct QuickTilesChanged;
 struct ActiveTile;
 
 pub struct SelectTile(pub usize);
-pub struct SelectColor(pub Palette, pub bool);
+pub struct SelectColor(pub TileColor, pub bool);
 
-#[derive(Resource)]
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct Selection {
     pub index: usize,
-    pub bg: Palette,
-    pub fg: Palette,
-    pub flip: bool,
+    pub bg: TileColor,
+    pub fg: TileColor,
+    #[serde(deserialize_with = "flip")]
+    pub flip: (bool, bool),
     pub rotation: u8,
 }
 
+/// Projects saved before vertical flips have a single horizontal flip.
+fn flip<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(bool, bool), D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flip {
+        Horizontal(bool),
+        Both(bool, bool),
+    }
+
+    Ok(match Flip::deserialize(deserializer)? {
+        Flip::Horizontal(x) => (x, false),
+        Flip::Both(x, y) => (x, y),
+    })
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection {
+            index: util::misc::DEFAULT_TILE,
+            bg: Palette::E.into(),
+            fg: Palette::A.into(),
+            flip: (false, false),
+            rotation: 0,
+        }
+    }
+}
+
 fn setup(
     mut commands: Commands,
     textures: Res<Textures>,
+    tilesets: Res<Tilesets>,
 ) {
-    // Quick tiles
-    let mut tiles = Vec::new();
-    let dx = 56.;
-    let per_row = util::misc::QUICK_TILES_PER_ROW;
-    let rows = util::misc::QUICK_TILES_ROWS;
-    for i in 0..(per_row * rows) {
-        let id = commands
-            .spawn(TextModeSpriteSheetBundle {
-                sprite: TextModeTextureAtlasSprite {
-                    bg: Color::BLACK,
-                    fg: Color::WHITE,
-                    alpha: 0.5,
-                    index: i,
-                    anchor: Anchor::BottomLeft,
-                    ..Default::default()
-                },
-                texture_atlas: textures.mrmotext.clone(),
-                transform: Transform::from_xyz(dx + (i % per_row) as f32 * 8., (8. * rows as f32) - (i / per_row) as f32 * 8., util::z::TOOLBAR_ICONS),
-                ..Default::default()
-            })
-            .insert(Clickable {
-                w: 8.,
-                h: 8.,
-                id: ButtonId::QuickTile(i),
-                hover_click: true,
-            })
-            .insert(QuickTile)
-            .insert(QuickTilesUI)
-            .id();
-        tiles.push(QuickTileId { index: i, tile: i, entity: id, });
-    }
-
+    let tiles = spawn_quick_tiles(&mut commands, &tilesets);
     commands.insert_resource(QuickTiles(tiles));
 
     // Active tile
     commands
         .spawn(TextModeSpriteSheetBundle {
             sprite: TextModeTextureAtlasSprite {
-                bg: Palette::E.color(),
-                fg: Palette::A.color(),
                 alpha: 1.,
                 index: util::misc::DEFAULT_TILE,
                 anchor: Anchor::BottomLeft,
                 ..Default::default()
             },
-            texture_atlas: textures.mrmotext.clone(),
+            texture_atlas: tilesets.atlas(),
             transform: Transform {
                 translation: Vec3::new(32., 16., util::z::TOOLBAR),
-                scale: Vec3::new(2., 2., 1.),
+                scale: active_tile_scale(&tilesets),
                 ..Default::default()
             },
             ..Default::default()
         })
+        .insert(Colored { bg: Palette::E.into(), fg: Palette::A.into() })
+        .insert(TilesetSprite)
         .insert(ActiveTile)
         .insert(QuickTilesUI);
 
-    // Palette
-    for (i, color) in util::Palette::iter().enumerate() {
+    // Palette, filled by [update_swatches]
+    for i in 0..util::misc::PALETTE_SLOTS {
         commands
             .spawn(SpriteBundle {
                 sprite: Sprite {
-                    color: color.color(),
                     anchor: Anchor::BottomLeft,
                     ..Default::default()
                 },
@@ -147,14 +148,109 @@ fn setup(
                 texture: textures.color.clone(),
                 ..Default::default()
             })
+            .insert(ColorButton(i))
+            .insert(QuickTilesUI);
+    }
+}
+
+/// Rows of tiles filling the quick tiles area, tiles too big for it are shrunk.
+fn spawn_quick_tiles(
+    commands: &mut Commands,
+    tilesets: &Tilesets,
+) -> Vec<QuickTileId> {
+    let (w, h) = (util::size::QUICK_TILES_W, util::size::QUICK_TILES_H);
+    let size = tilesets.active().tile_size();
+    let scale = (w / size.x).min(h / size.y).min(1.);
+    let size = size * scale;
+    let per_row = (w / size.x) as usize;
+    let rows = (h / size.y) as usize;
+
+    let mut tiles = Vec::new();
+    let dx = 56.;
+    let top = 8. + h;
+    for i in 0..(per_row * rows) {
+        let id = commands
+            .spawn(TextModeSpriteSheetBundle {
+                sprite: TextModeTextureAtlasSprite {
+                    bg: Color::BLACK,
+                    fg: Color::WHITE,
+                    alpha: 0.5,
+                    index: i,
+                    anchor: Anchor::BottomLeft,
+                    ..Default::default()
+                },
+                texture_atlas: tilesets.atlas(),
+                transform: Transform {
+                    translation: Vec3::new(dx + (i % per_row) as f32 * size.x, top - (i / per_row + 1) as f32 * size.y, util::z::TOOLBAR_ICONS),
+                    scale: Vec3::new(scale, scale, 1.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
             .insert(Clickable {
-                w: 8.,
-                h: 8.,
-                id: ButtonId::QuickColor(color),
-                hover_click: false,
+                w: size.x,
+                h: size.y,
+                id: ButtonId::QuickTile(i),
+                hover_click: true,
             })
-            .insert(ColorButton)
-            .insert(QuickTilesUI);
+            .insert(TilesetSprite)
+            .insert(QuickTile)
+            .insert(QuickTilesUI)
+            .id();
+        tiles.push(QuickTileId { index: i, tile: i, entity: id, });
+    }
+    tiles
+}
+
+/// The active tile is drawn in a 16x16 box.
+fn active_tile_scale(tilesets: &Tilesets) -> Vec3 {
+    let size = tilesets.active().tile_size();
+    let scale = 16. / size.x.max(size.y);
+    Vec3::new(scale, scale, 1.)
+}
+
+/// Page Up / Page Down browse palettes bigger than the panel.
+fn update_palette_page(
+    keys: Res<Input<KeyCode>>,
+    palette: Res<TilePalette>,
+    mut page: ResMut<PalettePage>,
+) {
+    // Colors, then transparent
+    let pages = (palette.colors.len() + util::misc::PALETTE_SLOTS) / util::misc::PALETTE_SLOTS;
+    if page.0 >= pages { page.0 = pages - 1; }
+
+    if keys.just_pressed(KeyCode::PageUp) && page.0 > 0 { page.0 -= 1; }
+    if keys.just_pressed(KeyCode::PageDown) && page.0 + 1 < pages { page.0 += 1; }
+}
+
+fn update_swatches(
+    mut commands: Commands,
+    palette: Res<TilePalette>,
+    page: Res<PalettePage>,
+    mut swatches: Query<(Entity, &ColorButton, &mut Sprite, &mut Visibility)>,
+) {
+    if !palette.is_changed() && !page.is_changed() { return; }
+
+    for (e, ColorButton(slot), mut sprite, mut visibility) in swatches.iter_mut() {
+        let entry = page.0 * util::misc::PALETTE_SLOTS + slot;
+        let color = match entry {
+            i if i < palette.colors.len() => TileColor::Index(i as u8),
+            i if i == palette.colors.len() => TileColor::Transparent,
+            _ => {
+                visibility.set_if_neq(Visibility::Hidden);
+                commands.entity(e).remove::<Clickable>();
+                continue;
+            }
+        };
+
+        sprite.color = palette.color(color);
+        visibility.set_if_neq(Visibility::Inherited);
+        commands.entity(e).insert(Clickable {
+            w: 8.,
+            h: 8.,
+            id: ButtonId::QuickColor(color),
+            hover_click: false,
+        });
     }
 }
 
@@ -176,9 +272,12 @@ fn on_click(
     mut select_tile: EventWriter<SelectTile>,
     mut select_color: EventWriter<SelectColor>,
     mut selection: ResMut<Selection>,
+    editor: Option<Res<PaletteEditor>>,
 ) {
     for Clicked(id, right) in clicked.iter() {
         match id {
+            // Covered by the palette editor
+            ButtonId::QuickTile(_) if editor.is_some() => (),
             ButtonId::QuickTile(n) => {
                 let Some(quick_tile) = quick_tiles.0.iter().find(|tile| tile.index == *n) else { continue };
                 selection.index = quick_tile.tile;
@@ -195,21 +294,37 @@ fn on_click(
 }
 
 fn update_range(
+    mut commands: Commands,
     keys: Res<Input<KeyCode>>,
     tool: Res<SelectedTool>,
+    tilesets: Res<Tilesets>,
     mut tiles: ResMut<QuickTiles>,
+    mut active_tile: Query<&mut Transform, With<ActiveTile>>,
     mut changed: EventWriter<QuickTilesChanged>,
 ) {
-    if tool.0 == Tools::Resize { return; }
+    // Start over with a new tileset, laid out for its tile size
+    if tilesets.is_changed() && !tilesets.is_added() {
+        for tile in tiles.0.iter() {
+            commands.entity(tile.entity).despawn_recursive();
+        }
+        tiles.0 = spawn_quick_tiles(&mut commands, &tilesets);
+        if let Ok(mut transform) = active_tile.get_single_mut() {
+            transform.scale = active_tile_scale(&tilesets);
+        }
+    }
+
+    if tool.0 == Tools::Resize || tool.0 == Tools::Select || tool.0 == Tools::Text { return; }
     let up = keys.just_pressed(KeyCode::Up);
     let down = keys.just_pressed(KeyCode::Down);
     if !up && !down { return; }
 
-    let count = util::misc::QUICK_TILES_PER_ROW * util::misc::QUICK_TILES_ROWS;
+    let count = tiles.0.len().max(1);
+    // Whole pages, tiles past the end of the tileset are blank
+    let total = ((tilesets.active().count() + count - 1) / count).max(1) * count;
     if up {
-        tiles.0.iter_mut().for_each(|tile| tile.tile = (tile.tile + util::misc::TILESET_COUNT - count) % util::misc::TILESET_COUNT);
+        tiles.0.iter_mut().for_each(|tile| tile.tile = (tile.tile + total - count) % total);
     } else {
-        tiles.0.iter_mut().for_each(|tile| tile.tile = (tile.tile + count) % util::misc::TILESET_COUNT);
+        tiles.0.iter_mut().for_each(|tile| tile.tile = (tile.tile + count) % total);
     }
     changed.send(QuickTilesChanged);
 }
@@ -235,34 +350,49 @@ fn update_active_tile(
     mut select_tile: EventReader<SelectTile>,
     mut tile: Query<&mut TextModeTextureAtlasSprite, With<ActiveTile>>,
     mut window: Query<&mut Window>,
+    mut chord: Local<bool>,
 ) {
     let mut tile = tile.single_mut();
 
-    if keys.just_pressed(KeyCode::LControl) {
-        selection.flip = !selection.flip;
-        tile.flip_x = selection.flip;
-    } else if keys.just_pressed(KeyCode::LAlt) {
+    // Ctrl and Alt only flip / rotate when tapped alone, so they can be used as modifiers
+    // Shift + Ctrl flips vertically
+    if keys.any_just_pressed([KeyCode::LControl, KeyCode::LAlt]) {
+        *chord = false;
+    } else if keys.get_just_pressed().next().is_some() {
+        *chord = true;
+    }
+
+    if keys.just_released(KeyCode::LControl) && !*chord {
+        if keys.pressed(KeyCode::LShift) {
+            selection.flip.1 = !selection.flip.1;
+        } else {
+            selection.flip.0 = !selection.flip.0;
+        }
+        tile.flip_x = selection.flip.0;
+        tile.flip_y = selection.flip.1;
+    } else if keys.just_released(KeyCode::LAlt) && !*chord {
         selection.rotation = (selection.rotation + 1) % 4;
         tile.rotation = selection.rotation;
     }
 
     for SelectTile(i) in select_tile.iter() {
         tile.index = *i;
-        tile.flip_x = selection.flip;
+        tile.flip_x = selection.flip.0;
+        tile.flip_y = selection.flip.1;
         tile.rotation = selection.rotation;
     }
 
-    window.single_mut().title = format!("rtemo (tile {}, flip {}, rotation {})", tile.index, tile.flip_x, tile.rotation);
+    window.single_mut().title = format!("rtemo (tile {}, flip {} {}, rotation {})", tile.index, tile.flip_x, tile.flip_y, tile.rotation);
 }
 
 fn update_colors(
     mut color: EventReader<SelectColor>,
-    mut sprites: Query<&mut TextModeTextureAtlasSprite, With<ActiveTile>>,
+    mut colored: Query<&mut Colored, With<ActiveTile>>,
 ) {
     for SelectColor(p, bg) in color.iter() {
-        let mut sprite = sprites.single_mut();
-        if *bg { sprite.bg = p.color(); }
-        else { sprite.fg = p.color(); }
+        let mut colored = colored.single_mut();
+        if *bg { colored.bg = *p; }
+        else { colored.fg = *p; }
     }
 }
 
@@ -273,4 +403,19 @@ fn cleanup(
     for e in query.iter() {
         commands.entity(e).despawn_recursive();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_flips() {
+        let selection = |flip: &str| ron::from_str::<Selection>(&format!("(index: 3, bg: A, fg: Index(2), flip: {}, rotation: 1)", flip)).unwrap().flip;
+        assert_eq!(selection("true"), (true, false));
+        assert_eq!(selection("(false, true)"), (false, true));
+
+        let saved = ron::to_string(&Selection { flip: (true, true), ..default() }).unwrap();
+        assert_eq!(ron::from_str::<Selection>(&saved).unwrap().flip, (true, true));
+    }
+}
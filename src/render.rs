@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use bevy::prelude::{Color, Resource};
+use image::{imageops, Rgba, RgbaImage};
+
+use crate::grid::Tile;
+use crate::palette::TilePalette;
+use crate::project::ProjectGrid;
+use crate::tilesets::Tileset;
+
+#[derive(Resource)]
+pub struct PngOptions {
+    /// Integer upscaling factor.
+    pub scale: u32,
+    /// Keep transparent colors transparent instead of drawing them over black.
+    pub transparent: bool,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions { scale: 1, transparent: true }
+    }
+}
+
+pub fn load_tileset(path: &Path) -> Result<RgbaImage, String> {
+    image::open(path)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn rgba(color: Color) -> Rgba<u8> {
+    Rgba(color.as_rgba_f32().map(|c| (c * 255.).round() as u8))
+}
+
+/// Draws [color] over [below], with [alpha] the opacity of its layer.
+fn blend(below: Rgba<u8>, color: Rgba<u8>, alpha: f32) -> Rgba<u8> {
+    let a = color[3] as f32 / 255. * alpha;
+    let b = below[3] as f32 / 255.;
+    let out = a + b * (1. - a);
+    if out <= 0. { return Rgba([0, 0, 0, 0]); }
+
+    let channel = |i: usize| ((color[i] as f32 * a + below[i] as f32 * b * (1. - a)) / out).round() as u8;
+    Rgba([channel(0), channel(1), channel(2), (out * 255.).round() as u8])
+}
+
+/// Same test as the text mode shader: pixels with some red are glyph pixels.
+fn is_fg(pixel: &Rgba<u8>) -> bool {
+    pixel[0] != 0
+}
+
+/// Tileset cell of the tile, rotated by clockwise quarter turns then flipped, like bevy_text_mode does.
+fn glyph(tileset: &Tileset, image: &RgbaImage, tile: &Tile) -> RgbaImage {
+    let (w, h) = (tileset.tile_width as u32, tileset.tile_height as u32);
+    let (column, row) = ((tile.index % tileset.columns) as u32, (tile.index / tileset.columns) as u32);
+    let (x, y) = (column * (w + tileset.padding.0 as u32), row * (h + tileset.padding.1 as u32));
+    if tile.index >= tileset.count() || x + w > image.width() || y + h > image.height() { return RgbaImage::new(w, h); }
+
+    let mut glyph = imageops::crop_imm(image, x, y, w, h).to_image();
+    for _ in 0..(tile.rotation % 4) { glyph = imageops::rotate90(&glyph); }
+    if tile.flip.0 { glyph = imageops::flip_horizontal(&glyph); }
+    if tile.flip.1 { glyph = imageops::flip_vertical(&glyph); }
+    glyph
+}
+
+/// Draws the grid as it looks in the editor, without needing a window.
+pub fn render(grid: &ProjectGrid, palette: &TilePalette, tileset: &Tileset, tileset_image: &RgbaImage, options: &PngOptions) -> RgbaImage {
+    let (w, h) = (tileset.tile_width as u32, tileset.tile_height as u32);
+    let mut image = RgbaImage::new(grid.w as u32 * w, grid.h as u32 * h);
+
+    for layer in grid.layers.iter().filter(|l| l.visible) {
+        for ((x, y), tile) in layer.tiles.iter() {
+            let (dx, dy) = (x - grid.x0, y - grid.y0);
+            if dx < 0 || dy < 0 || dx >= grid.w as isize || dy >= grid.h as isize { continue }
+
+            let (fg, bg) = (rgba(palette.color(tile.fg)), rgba(palette.color(tile.bg)));
+            for (px, py, pixel) in glyph(tileset, tileset_image, tile).enumerate_pixels() {
+                // Quarter turns of tiles that aren't square
+                if px >= w || py >= h { continue }
+                let color = if is_fg(pixel) { fg } else { bg };
+                let below = image.get_pixel_mut(dx as u32 * w + px, dy as u32 * h + py);
+                *below = blend(*below, color, layer.alpha);
+            }
+        }
+    }
+
+    if !options.transparent {
+        for pixel in image.pixels_mut() {
+            let a = pixel[3] as u32;
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| (c as u32 * a / 255) as u8);
+            *pixel = Rgba([r, g, b, 255]);
+        }
+    }
+
+    if options.scale > 1 {
+        image = imageops::resize(&image, image.width() * options.scale, image.height() * options.scale, imageops::FilterType::Nearest);
+    }
+
+    image
+}
+
+pub fn save_png(grid: &ProjectGrid, palette: &TilePalette, tileset: &Tileset, path: &Path, options: &PngOptions) -> Result<(), String> {
+    let tileset_image = load_tileset(&tileset.image_path())?;
+    render(grid, palette, tileset, &tileset_image, options)
+        .save(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
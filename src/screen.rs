@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowResized};
+
+use crate::{HEIGHT, WIDTH};
+use crate::grid::ZoomChanged;
+use crate::toolbar::UpdateToolbar;
+
+pub struct ScreenPlugin;
+
+impl Plugin for ScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Screen { width: WIDTH, height: HEIGHT })
+            .insert_resource(Cursor(None))
+            .add_system(fit_camera.in_base_set(CoreSet::PreUpdate))
+            .add_system(update_cursor.in_base_set(CoreSet::PreUpdate).after(fit_camera));
+    }
+}
+
+/// Size of the window in world units, a pixel of the interface font being one unit.
+/// It is at least [WIDTH] x [HEIGHT], the bottom left corner of the window is the origin.
+#[derive(Resource)]
+pub struct Screen {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Screen {
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+}
+
+/// Cursor position in world units.
+#[derive(Resource)]
+pub struct Cursor(pub Option<Vec2>);
+
+/// Scales the camera by a whole number of physical pixels per unit, as big as the window allows.
+fn fit_camera(
+    mut resized: EventReader<WindowResized>,
+    mut screen: ResMut<Screen>,
+    mut update_toolbar: EventWriter<UpdateToolbar>,
+    mut zoom_changed: EventWriter<ZoomChanged>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut ready: Local<bool>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    if resized.iter().last().is_none() && *ready { return; }
+    let (Ok(window), Ok(mut transform)) = (windows.get_single(), camera.get_single_mut()) else { return };
+    *ready = true;
+
+    let factor = window.scale_factor() as f32;
+    let pixels = ((window.width() / WIDTH).min(window.height() / HEIGHT) * factor).floor().max(1.) / factor;
+    let (width, height) = (window.width() / pixels, window.height() / pixels);
+    transform.scale = Vec3::new(1. / pixels, 1. / pixels, 1.);
+    transform.translation = Vec3::new(width / 2., height / 2., transform.translation.z);
+
+    if screen.width != width || screen.height != height {
+        *screen = Screen { width, height };
+        update_toolbar.send(UpdateToolbar);
+        zoom_changed.send(ZoomChanged);
+    }
+}
+
+fn update_cursor(
+    mut cursor: ResMut<Cursor>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+) {
+    let (Ok(window), Ok((camera, transform))) = (windows.get_single(), camera.get_single()) else { return };
+    let position = window.cursor_position()
+        .and_then(|p| camera.viewport_to_world(transform, p))
+        .map(|ray| ray.origin.truncate());
+    if cursor.0 != position { cursor.0 = position; }
+}
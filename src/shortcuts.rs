@@ -0,0 +1,92 @@
+use std::fs;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::{AppState, util};
+use crate::toolbar::Tool;
+use crate::tools::{ActivateTool, TextCursorState};
+use crate::ui::{Prompt, ShowMessage};
+
+pub struct ShortcutsPlugin;
+
+impl Plugin for ShortcutsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Keymap::load())
+            .add_systems((apply_keymap, dispatch).chain().in_set(OnUpdate(AppState::Editor)));
+    }
+}
+
+/// Shortcut overrides read from [util::misc::KEYMAP], indexed by tool name:
+/// ```ron
+/// {
+///     "Pencil": 'd',
+///     "Export": 'x',
+/// }
+/// ```
+#[derive(Resource, Default)]
+pub struct Keymap(HashMap<String, char>);
+
+impl Keymap {
+    fn load() -> Self {
+        let Ok(text) = fs::read_to_string(util::misc::KEYMAP) else { return Keymap::default() };
+        match ron::from_str::<HashMap<String, char>>(&text) {
+            Ok(map) => Keymap(map.into_iter().map(|(tool, c)| (tool, c.to_ascii_lowercase())).collect()),
+            Err(e) => {
+                warn!("Couldn't read {}: {}", util::misc::KEYMAP, e);
+                Keymap::default()
+            }
+        }
+    }
+}
+
+/// Overrides shortcuts of new tools and reports keys claimed by several tools.
+fn apply_keymap(
+    keymap: Res<Keymap>,
+    mut message: EventWriter<ShowMessage>,
+    mut added: Query<&mut Tool, Added<Tool>>,
+    tools: Query<&Tool>,
+) {
+    if added.is_empty() { return; }
+
+    for mut tool in added.iter_mut() {
+        if let Some(c) = keymap.0.get(&tool.id.name()) { tool.shortcut = *c; }
+    }
+
+    let mut claimed: HashMap<char, Vec<String>> = HashMap::new();
+    for tool in tools.iter() {
+        claimed.entry(tool.shortcut).or_default().push(tool.id.name());
+    }
+    let mut conflicts = claimed.iter()
+        .filter(|(_, tools)| tools.len() > 1)
+        .collect::<Vec<(&char, &Vec<String>)>>();
+    conflicts.sort_by_key(|(c, _)| **c);
+    for (c, tools) in conflicts {
+        message.send(ShowMessage(format!("Shortcut '{}' used by {}", c, tools.join(", "))));
+    }
+}
+
+/// Keys without modifiers activate the tool with the matching shortcut,
+/// the one with the lowest priority if several tools share it.
+fn dispatch(
+    keys: Res<Input<KeyCode>>,
+    tools: Query<&Tool>,
+    prompt: Option<Res<Prompt>>,
+    cursor: Option<Res<TextCursorState>>,
+    mut activate: EventWriter<ActivateTool>,
+) {
+    if prompt.is_some() || cursor.is_some() { return; }
+    let modifiers = [KeyCode::LControl, KeyCode::RControl, KeyCode::LAlt, KeyCode::RAlt, KeyCode::LWin, KeyCode::RWin];
+    if keys.any_pressed(modifiers) { return; }
+
+    for key in keys.get_just_pressed() {
+        let Some(c) = util::get_char(key) else { continue };
+        let tool = tools.iter()
+            .filter(|tool| tool.shortcut == c)
+            .min_by_key(|tool| tool.priority);
+        if let Some(tool) = tool {
+            activate.send(ActivateTool(tool.id));
+        }
+    }
+}
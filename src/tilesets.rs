@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_text_mode::TextModeTextureAtlasSprite;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{AppState, util};
+use crate::grid::{Grid, GridChanged, GridResized};
+use crate::loading::Textures;
+use crate::quick_tiles::{Selection, SelectTile};
+use crate::ui::ShowMessage;
+
+pub struct TilesetsPlugin;
+
+impl Plugin for TilesetsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(RonAssetPlugin::<Tileset>::new(&["tileset.ron"]))
+            .add_event::<SwitchTileset>()
+            .add_system(switch.in_set(OnUpdate(AppState::Editor)))
+            .add_system(apply_tileset.in_base_set(CoreSet::PostUpdate));
+    }
+}
+
+/// Descriptor of a tileset, `assets/tilesets/*.tileset.ron`:
+/// ```ron
+/// (
+///     name: "MRMOTEXT EX",
+///     image: "MRMOTEXT EX.png",
+///     tile_width: 8,
+///     tile_height: 8,
+///     columns: 32,
+///     rows: 32,
+///     charmap: (runs: [(865, "!\"#$%")], uppercase: true, fallback: Some(895)),
+/// )
+/// ```
+/// A font laid out like DOS uses `charmap: (tables: [(0, "cp437")])`.
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "5d3f7a52-9c1e-4b8a-a0f6-3e2b8c71d4e9"]
+pub struct Tileset {
+    pub name: String,
+    /// Relative to the assets folder.
+    pub image: String,
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub columns: usize,
+    pub rows: usize,
+    /// Pixels between two columns and two rows.
+    #[serde(default)]
+    pub padding: (usize, usize),
+    #[serde(default)]
+    pub charmap: Charmap,
+    /// Custom styles of the frame tool.
+    #[serde(default)]
+    pub frames: Vec<Frame>,
+}
+
+/// Charmap of a tileset descriptor.
+#[derive(Deserialize, Default)]
+struct CharmapDescriptor {
+    /// Characters drawn by consecutive tiles, from the given tile.
+    #[serde(default)]
+    runs: Vec<(usize, String)>,
+    /// Tables "cp437", "petscii" or "ascii" (from the space) placed from the given tile, runs come first.
+    #[serde(default)]
+    tables: Vec<(usize, String)>,
+    /// Lowercase letters use the uppercase glyphs, for fonts without lowercase.
+    #[serde(default)]
+    uppercase: bool,
+    /// Tile of the characters the charmap doesn't have.
+    #[serde(default)]
+    fallback: Option<usize>,
+}
+
+/// Tiles of the characters typed with the text tool, and of text exports.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(from = "CharmapDescriptor")]
+pub struct Charmap {
+    /// Runs then tables, expanded when the tileset is loaded.
+    runs: Vec<(usize, Vec<char>)>,
+    /// First tile of each character.
+    tiles: HashMap<char, usize>,
+    uppercase: bool,
+    fallback: Option<usize>,
+}
+
+impl From<CharmapDescriptor> for Charmap {
+    fn from(descriptor: CharmapDescriptor) -> Self {
+        let runs = descriptor.runs.into_iter()
+            .map(|(start, chars)| (start, chars.chars().collect()))
+            .chain(descriptor.tables.iter().map(|(start, name)| (*start, Charmap::table(name))))
+            .collect::<Vec<(usize, Vec<char>)>>();
+
+        let mut tiles = HashMap::new();
+        for (start, chars) in runs.iter() {
+            for (i, c) in chars.iter().enumerate() {
+                tiles.entry(*c).or_insert(start + i);
+            }
+        }
+
+        Charmap { runs, tiles, uppercase: descriptor.uppercase, fallback: descriptor.fallback }
+    }
+}
+
+/// Tiles of a frame style.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Frame {
+    pub name: String,
+    /// Top left, top, top right, right, bottom right, bottom, bottom left and left.
+    /// With only the top left corner and the top edge, the others are flipped and rotated.
+    #[serde(deserialize_with = "frame_tiles")]
+    pub tiles: Vec<usize>,
+}
+
+fn frame_tiles<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<usize>, D::Error> {
+    let tiles = Vec::<usize>::deserialize(deserializer)?;
+    if tiles.len() != 2 && tiles.len() != 8 {
+        return Err(de::Error::invalid_length(tiles.len(), &"2 or 8 frame tiles"));
+    }
+    Ok(tiles)
+}
+
+impl Charmap {
+    fn table(name: &str) -> Vec<char> {
+        match name.to_ascii_lowercase().as_str() {
+            "cp437" => CP437.to_vec(),
+            "petscii" => PETSCII.chars().collect(),
+            "ascii" => (' '..='~').collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Tile drawing this character, if there is one.
+    pub fn tile(&self, c: char) -> Option<usize> {
+        let mut upper = c.to_uppercase();
+        let c = match (upper.next(), upper.next()) {
+            (Some(u), None) if self.uppercase => u,
+            _ => c,
+        };
+        self.tiles.get(&c).copied()
+    }
+
+    /// Tile drawing this character, or the fallback one.
+    pub fn glyph(&self, c: char) -> Option<usize> {
+        self.tile(c).or(self.fallback)
+    }
+
+    /// Character of a tile, blank glyphs have none.
+    pub fn char(&self, index: usize) -> Option<char> {
+        self.runs.iter()
+            .find_map(|(start, chars)| chars.get(index.checked_sub(*start)?).copied())
+            .filter(|c| !c.is_control())
+    }
+}
+
+/// Code page 437 in order, 0 is a blank glyph.
+pub const CP437: [char; 256] = [
+    '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    '\u{a0}', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Screen codes 0 to 63 of the uppercase PETSCII set.
+const PETSCII: &str = "@ABCDEFGHIJKLMNOPQRSTUVWXYZ[£]↑← !\"#$%&'()*+,-./0123456789:;<=>?";
+
+impl Default for Tileset {
+    /// MRMOTEXT, used when no descriptor is found.
+    fn default() -> Self {
+        Tileset {
+            name: util::misc::DEFAULT_TILESET.to_string(),
+            image: util::misc::TILESET.to_string(),
+            tile_width: 8,
+            tile_height: 8,
+            columns: 32,
+            rows: 32,
+            padding: (0, 0),
+            charmap: Charmap::from(CharmapDescriptor {
+                runs: vec![(865, (b'!'..=b'~').map(|c| c as char).collect())],
+                uppercase: true,
+                fallback: Some(895),
+                ..Default::default()
+            }),
+            frames: vec![],
+        }
+    }
+}
+
+impl Tileset {
+    pub fn tile_size(&self) -> Vec2 {
+        Vec2::new(self.tile_width as f32, self.tile_height as f32)
+    }
+
+    pub fn count(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    /// Tile drawn in place of the ones this tileset doesn't have: its space, else its fallback glyph.
+    pub fn blank(&self) -> usize {
+        [self.charmap.tile(' '), self.charmap.fallback].into_iter().flatten()
+            .find(|i| *i < self.count())
+            .unwrap_or(0)
+    }
+
+    /// Projects saved before tileset descriptors refer to the image.
+    pub fn is(&self, name: &str) -> bool {
+        self.name == name || self.image == name
+    }
+
+    pub fn atlas(&self, image: Handle<Image>) -> TextureAtlas {
+        TextureAtlas::from_grid(
+            image,
+            self.tile_size(),
+            self.columns,
+            self.rows,
+            Some(Vec2::new(self.padding.0 as f32, self.padding.1 as f32)),
+            None,
+        )
+    }
+
+    pub fn image_path(&self) -> PathBuf {
+        Path::new(util::misc::ASSETS).join(&self.image)
+    }
+
+    pub fn parse(text: &str) -> Result<Tileset, String> {
+        ron::from_str::<Tileset>(text).map_err(|e| e.to_string())
+    }
+
+    /// Reads the descriptors of the tilesets folder, without the asset server.
+    pub fn find(name: &str) -> Option<Tileset> {
+        let dir = Path::new(util::misc::ASSETS).join(util::misc::TILESETS);
+        fs::read_dir(dir).ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tileset.ron"))
+            .filter_map(|entry| Tileset::parse(&fs::read_to_string(entry.path()).ok()?).ok())
+            .find(|tileset| tileset.is(name))
+            .or_else(|| Some(Tileset::default()).filter(|tileset| tileset.is(name)))
+    }
+}
+
+/// Loaded tilesets, sorted by name.
+#[derive(Resource)]
+pub struct Tilesets {
+    pub list: Vec<(Tileset, Handle<TextureAtlas>)>,
+    pub active: usize,
+}
+
+impl Tilesets {
+    pub fn active(&self) -> &Tileset {
+        &self.list[self.active].0
+    }
+
+    pub fn atlas(&self) -> Handle<TextureAtlas> {
+        self.list[self.active].1.clone()
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.list.iter().position(|(tileset, _)| tileset.is(name))
+    }
+}
+
+impl FromWorld for Tilesets {
+    fn from_world(world: &mut World) -> Self {
+        let handles = world.resource::<Textures>().tilesets.clone();
+        let descriptors = world.resource::<Assets<Tileset>>();
+        let mut tilesets = handles.iter()
+            .filter_map(|handle| descriptors.get(handle).cloned())
+            .collect::<Vec<Tileset>>();
+        if tilesets.is_empty() { tilesets.push(Tileset::default()); }
+        tilesets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut list = vec![];
+        for tileset in tilesets {
+            let image = world.resource::<AssetServer>().load(tileset.image.as_str());
+            let atlas = world.resource_mut::<Assets<TextureAtlas>>().add(tileset.atlas(image));
+            list.push((tileset, atlas));
+        }
+
+        let active = list.iter().position(|(tileset, _)| tileset.is(util::misc::DEFAULT_TILESET)).unwrap_or(0);
+        Tilesets { list, active }
+    }
+}
+
+/// Activates the tileset at this index of [Tilesets::list].
+pub struct SwitchTileset(pub usize);
+
+fn switch(
+    mut ev: EventReader<SwitchTileset>,
+    mut tilesets: ResMut<Tilesets>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut grid_resized: EventWriter<GridResized>,
+    mut select_tile: EventWriter<SelectTile>,
+    mut message: EventWriter<ShowMessage>,
+    selection: Res<Selection>,
+    grid: Option<Res<Grid>>,
+) {
+    let Some(grid) = grid else { return };
+    for SwitchTileset(i) in ev.iter() {
+        if *i >= tilesets.list.len() || *i == tilesets.active { continue }
+        tilesets.active = *i;
+
+        // Cells follow the tile size, and tiles blanked by a smaller tileset get their glyph back
+        grid_resized.send(GridResized);
+        grid_changed.send(GridChanged(grid.cells.keys().copied().collect()));
+        select_tile.send(SelectTile(selection.index));
+        message.send(ShowMessage(format!("Tileset {} ({} tiles)", tilesets.active().name, tilesets.active().count())));
+    }
+}
+
+/// Text mode sprite drawn with the active tileset.
+#[derive(Component)]
+pub struct TilesetSprite;
+
+/// Switches atlases with the active tileset, tiles it doesn't have show its blank tile.
+fn apply_tileset(
+    tilesets: Option<Res<Tilesets>>,
+    mut sprites: Query<(&mut Handle<TextureAtlas>, &mut TextModeTextureAtlasSprite), With<TilesetSprite>>,
+) {
+    let Some(tilesets) = tilesets else { return };
+    let (count, blank) = (tilesets.active().count(), tilesets.active().blank());
+    for (mut atlas, mut sprite) in sprites.iter_mut() {
+        if tilesets.is_changed() { *atlas = tilesets.atlas(); }
+        if (tilesets.is_changed() || sprite.is_changed()) && sprite.index >= count { sprite.index = blank; }
+    }
+}
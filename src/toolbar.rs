@@ -1,9 +1,10 @@
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
-use crate::{AppState, HEIGHT, tools, util};
+use crate::{AppState, tools, util};
 use crate::loading::Textures;
 use crate::mouse::{ButtonId, Clicked, Hover};
+use crate::screen::Screen;
 use crate::tools::ActivateTool;
 
 pub struct ToolbarPlugin;
@@ -16,7 +17,7 @@ impl Plugin for ToolbarPlugin {
             .add_event::<UpdateToolbar>()
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
             .add_systems(
-                (update_gui, update_toolbar, on_click)
+                (update_gui, update_toolbar, on_click, on_activate)
                     .in_set(OnUpdate(AppState::Editor))
             )
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
@@ -49,6 +50,7 @@ fn update_toolbar(
     mut ev: EventReader<UpdateToolbar>,
     mut items: ResMut<ToolbarItems>,
     tools: Query<(Entity, &Tool)>,
+    screen: Res<Screen>,
     mut transform: Query<&mut Transform>,
 ) {
     for _ in ev.iter() {
@@ -61,14 +63,17 @@ fn update_toolbar(
         t.sort_by_key(|(_, t)| t.priority);
         t.iter().for_each(|(e, _)| items.0.push(ToolbarItem::Tool(e.clone())));
 
-        // Display items
+        // Display items, on two columns when they don't fit above the palette
+        let columns = if items.0.len() > util::misc::TOOLBAR_ROWS { 2 } else { 1 };
+        let rows = (items.0.len() + columns - 1) / columns;
         for (n, item) in items.0.iter().enumerate() {
             match *item {
                 ToolbarItem::Tool(e) => {
                     if let Ok(mut t) = transform.get_mut(e) {
-                        t.translation.x = 8.;
-                        t.translation.y = HEIGHT - ((HEIGHT - items.0.len() as f32 * util::size::ICON - 72.) / 2.
-                            + n as f32 * util::size::ICON) - util::size::ICON;
+                        let (column, row) = (n % columns, n / columns);
+                        t.translation.x = 8. - (columns - 1) as f32 * util::size::ICON / 2. + column as f32 * util::size::ICON;
+                        t.translation.y = screen.height - ((screen.height - rows as f32 * util::size::ICON - 72.) / 2.
+                            + row as f32 * util::size::ICON) - util::size::ICON;
                     }
                 }
             }
@@ -163,6 +168,24 @@ fn on_click(
     }
 }
 
+/// Selects tools, or forwards a click to tools that can't be selected.
+fn on_activate(
+    mut ev: EventReader<ActivateTool>,
+    mut clicked: EventWriter<Clicked>,
+    mut update: EventWriter<UpdateToolbar>,
+    mut selected: ResMut<SelectedTool>,
+) {
+    for ActivateTool(tool) in ev.iter() {
+        if tool.is_selectable() {
+            if selected.0 == *tool { continue }
+            selected.0 = *tool;
+            update.send(UpdateToolbar);
+        } else {
+            clicked.send(Clicked(ButtonId::Tool(*tool), false));
+        }
+    }
+}
+
 fn cleanup(
     mut commands: Commands,
     query: Query<Entity, With<ToolbarUI>>,
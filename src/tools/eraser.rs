@@ -2,7 +2,7 @@ use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
 use crate::{AppState, mouse, util};
-use crate::grid::{Grid, GridChanged};
+use crate::grid::{Grid, GridChanged, Tile};
 use crate::loading::Textures;
 use crate::mouse::{ButtonId, Clicked};
 use crate::quick_tiles::Selection;
@@ -63,12 +63,17 @@ fn update(
     if tool.0 != Tools::Eraser { clicks.clear(); return; }
     for Clicked(id, _) in clicks.iter() {
         if let ButtonId::Grid(x, y) = id {
-            let Some((ref mut tile, _)) = grid.tiles.get_mut(&(*x, *y)) else { continue };
+            let upper_layer = grid.active > 0;
+            let Some(tile) = grid.tiles_mut().get_mut(&(*x, *y)) else { continue };
 
-            // Erase tile
-            tile.bg = selection.bg;
-            tile.fg = selection.fg;
-            tile.index = 0;
+            // Erase tile, upper layers become see-through
+            if upper_layer {
+                *tile = Tile::empty();
+            } else {
+                tile.bg = selection.bg;
+                tile.fg = selection.fg;
+                tile.index = 0;
+            }
 
             grid_changed.send(GridChanged(vec![(*x, *y)]));
         }
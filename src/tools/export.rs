@@ -1,24 +1,30 @@
-use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 
 use bevy::prelude::*;
 use bevy::reflect::List;
 use bevy::sprite::Anchor;
-use strum::IntoEnumIterator;
 
-use crate::{AppState, mouse, util};
+use crate::{AppState, convert, mouse, util};
+use crate::convert::Format;
 use crate::grid::{Grid, Tile};
 use crate::loading::Textures;
 use crate::mouse::{ButtonId, Clicked};
+use crate::palette::TilePalette;
+use crate::project::{self, Document, ProjectGrid};
+use crate::render::{self, PngOptions};
+use crate::tilesets::Tilesets;
 use crate::tools::Tools;
-use crate::util::Palette;
+use crate::ui::{OpenPrompt, PromptKind, PromptSubmitted, ShowMessage};
 
 pub(crate) struct ExportPlugin;
 
 impl Plugin for ExportPlugin {
     fn build(&self, app: &mut App) {
         app
+            .insert_resource(PngOptions::default())
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
-            .add_system(update.in_set(OnUpdate(AppState::Editor)))
+            .add_systems((update, on_prompt).in_set(OnUpdate(AppState::Editor)))
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
     }
 }
@@ -42,7 +48,7 @@ fn setup(
         ))
         .insert(crate::toolbar::Tool {
             id: Tools::Export,
-            shortcut: 'r',
+            shortcut: 'x',
             priority: util::tool_priority::EXPORT,
         })
         .insert(mouse::Clickable {
@@ -53,33 +59,129 @@ fn setup(
         });
 }
 
+/// Left click copies the grid to the clipboard, right click opens the export prompt,
+/// e.g. `drawing.png x4` for a 4 times bigger image, `ansi256 clipboard` or `ans drawing.ans`.
 fn update(
     mut clicked: EventReader<Clicked>,
+    mut prompt: EventWriter<OpenPrompt>,
+    mut message: EventWriter<ShowMessage>,
     grid: Option<Res<Grid>>,
+    document: Res<Document>,
+    palette: Res<TilePalette>,
 ) {
     let Some(grid) = grid else { return; };
     for Clicked(id, right) in clicked.iter() {
-        if *right { continue }
         let ButtonId::Tool(Tools::Export) = id else { continue };
 
-        // Index palette
-        let mut palette: HashMap<Palette, usize> = HashMap::new();
-        Palette::iter().enumerate().for_each(|(i, p)| { palette.insert(p, i); });
+        if *right {
+            message.send(ShowMessage("Formats: png, text, ansi, ansi256, ans, tuples; to a file or the clipboard".to_string()));
+            prompt.send(OpenPrompt {
+                kind: PromptKind::Export,
+                label: "Export:".to_string(),
+                default: document.path.as_ref()
+                    .map(|p| p.with_extension("png").to_string_lossy().to_string())
+                    .unwrap_or(util::misc::DEFAULT_PNG.to_string()),
+            });
+            continue;
+        }
+
+        // Visible layers, merged
+        let tiles = ProjectGrid::from_grid(&grid).flatten();
 
-        // Grid tiles
-        let mut tiles = grid.tiles.iter().collect::<Vec<(&(isize, isize), &(Tile, Entity))>>();
-        tiles.sort_by_key(|((x, y), _)| (*y - grid.y0) * 32 + (*x - grid.x0));
+        let export = to_tuples(tiles.iter().map(|((x, y), tile)| {
+            ((x - grid.x0) as usize, (grid.h as isize - 1 - (y - grid.y0)) as usize, tile)
+        }), &palette);
 
-        let mut export = "[\n".to_string();
-        for (&(x, y), (tile, _)) in tiles.iter() {
-            let (Some(bg), Some(fg)) = (palette.get(&tile.bg), palette.get(&tile.fg)) else { continue };
-            info!("({}, {}, {}, {}, {}, {}, {}),", x - grid.x0, grid.h as isize - 1 - (y - grid.y0), tile.index, bg, fg, tile.flip.0, tile.rotation);
-            export += &format!("    ({}, {}, {}, {}, {}, {}, {}),\n", x - grid.x0, grid.h as isize - 1 - (y - grid.y0), tile.index, bg, fg, tile.flip.0, tile.rotation);
+        match cli_clipboard::set_contents(export) {
+            Ok(_) => message.send(ShowMessage("Copied as Tuples".to_string())),
+            Err(e) => message.send(ShowMessage(format!("Couldn't export: {}", e))),
         }
-        export += "];";
+    }
+}
+
+/// `[format] <file or clipboard> [xN]`, the format defaults to the one of the file extension.
+fn parse_export(input: &str) -> Result<(Format, Option<PathBuf>, Option<u32>), String> {
+    let mut words = input.split_whitespace().collect::<Vec<&str>>();
+    // A lone format name goes to the clipboard
+    let format = words.first()
+        .filter(|w| words.len() > 1 || !w.contains('.'))
+        .and_then(|w| Format::from_name(w));
+    if format.is_some() { words.remove(0); }
+
+    // A trailing "x4" upscales PNG images
+    let scale = words.last()
+        .and_then(|w| w.strip_prefix('x'))
+        .and_then(|n| n.parse::<u32>().ok());
+    if scale.is_some() { words.pop(); }
+
+    let target = words.join(" ");
+    let path = Some(PathBuf::from(&target)).filter(|_| !target.is_empty() && target != "clipboard");
+    let format = format
+        .or_else(|| path.as_ref().and_then(|p| Format::from_path(p)))
+        .ok_or(format!("Unknown format for {}", target))?;
+    Ok((format, path, scale.map(|s| s.max(1))))
+}
+
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut message: EventWriter<ShowMessage>,
+    grid: Option<Res<Grid>>,
+    document: Res<Document>,
+    options: Res<PngOptions>,
+    palette: Res<TilePalette>,
+    tilesets: Res<Tilesets>,
+) {
+    let Some(grid) = grid else { return; };
+    for PromptSubmitted(kind, input) in submitted.iter() {
+        if *kind != PromptKind::Export || input.is_empty() { continue }
+
+        let (format, path, scale) = match parse_export(input) {
+            Ok(export) => export,
+            Err(e) => {
+                message.send(ShowMessage(e));
+                continue;
+            }
+        };
+        let project_grid = ProjectGrid::from_grid(&grid);
+
+        let result = match (format, path) {
+            (Format::Png, Some(path)) => {
+                let options = PngOptions { scale: scale.unwrap_or(options.scale), transparent: options.transparent };
+                let path = path.with_extension("png");
+                render::save_png(&project_grid, &palette, tilesets.active(), &path, &options)
+                    .map(|_| format!("Exported {}", project::file_name(&path)))
+            }
+            (Format::Png | Format::Ans, None) => Err(format!("{:?} can only be exported to a file", format)),
+            (Format::Project, _) => Err("Projects are written by the save tool".to_string()),
+            (format, None) => {
+                let title = document.path.as_ref().map(|p| convert::title(p)).unwrap_or_default();
+                convert::export(&project_grid, &palette, tilesets.active(), format, &title)
+                    .and_then(|bytes| String::from_utf8(bytes).map_err(|e| e.to_string()))
+                    .and_then(|text| cli_clipboard::set_contents(text).map_err(|e| e.to_string()))
+                    .map(|_| format!("Copied as {:?}", format))
+            }
+            (format, Some(path)) => {
+                convert::export(&project_grid, &palette, tilesets.active(), format, &convert::title(&path))
+                    .and_then(|bytes| fs::write(&path, bytes).map_err(|e| e.to_string()))
+                    .map(|_| format!("Exported {}", project::file_name(&path)))
+            }
+        };
+        match result {
+            Ok(done) => message.send(ShowMessage(done)),
+            Err(e) => message.send(ShowMessage(format!("Couldn't export: {}", e))),
+        }
+    }
+}
 
-        cli_clipboard::set_contents(export.to_string()).expect("Couldn't export to clipboard.");
+/// Formats tiles as a list of [util::TILE], `y` going up.
+pub(crate) fn to_tuples<'a>(tiles: impl Iterator<Item=(usize, usize, &'a Tile)>, palette: &TilePalette) -> String {
+    let mut export = "[\n".to_string();
+    for (x, y, tile) in tiles {
+        let (bg, fg) = (palette.to_tuple(tile.bg), palette.to_tuple(tile.fg));
+        export += &format!("    ({}, {}, {}, {}, {}, {}, {}, {}),\n", x, y, tile.index, bg, fg, tile.flip.0, tile.rotation, tile.flip.1);
     }
+    export += "];";
+    export
 }
 
 fn cleanup() {
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::utils::HashSet;
+
+use crate::{AppState, mouse, util};
+use crate::grid::{Grid, GridChanged, Tile};
+use crate::loading::Textures;
+use crate::mouse::{ButtonId, Clicked};
+use crate::quick_tiles::Selection;
+use crate::toolbar::SelectedTool;
+use crate::tools::Tools;
+use crate::ui::ShowMessage;
+
+pub(crate) struct FillPlugin;
+
+impl Plugin for FillPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(FillSettings {
+                index: true,
+                fg: true,
+                bg: true,
+                diagonal: false,
+            })
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems((update, settings).in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+/// Which properties neighbours must share with the clicked tile to be filled.
+#[derive(Resource)]
+pub struct FillSettings {
+    pub index: bool,
+    pub fg: bool,
+    pub bg: bool,
+    pub diagonal: bool,
+}
+
+impl FillSettings {
+    fn matches(&self, a: &Tile, b: &Tile) -> bool {
+        (!self.index || (a.index == b.index && a.flip == b.flip && a.rotation == b.rotation))
+            && (!self.fg || a.fg == b.fg)
+            && (!self.bg || a.bg == b.bg)
+    }
+
+    fn describe(&self) -> String {
+        let criteria = [(self.index, "tile"), (self.fg, "fg"), (self.bg, "bg")]
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, name)| *name)
+            .collect::<Vec<&str>>();
+        let criteria = if criteria.is_empty() { "any".to_string() } else { criteria.join("+") };
+        format!("Fill: match {}, {}-way", criteria, if self.diagonal { 8 } else { 4 })
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 5,
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Fill,
+            shortcut: 'f',
+            priority: util::tool_priority::FILL,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Fill),
+            hover_click: false,
+        });
+}
+
+/// 1, 2 and 3 toggle matching on tile, foreground and background, 4 and 8 set the connectivity.
+fn settings(
+    tool: Res<SelectedTool>,
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<FillSettings>,
+    mut message: EventWriter<ShowMessage>,
+) {
+    if tool.0 != Tools::Fill { return; }
+    if keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) { return; }
+
+    let mut changed = true;
+    if keys.just_pressed(KeyCode::Key1) { settings.index = !settings.index; }
+    else if keys.just_pressed(KeyCode::Key2) { settings.fg = !settings.fg; }
+    else if keys.just_pressed(KeyCode::Key3) { settings.bg = !settings.bg; }
+    else if keys.just_pressed(KeyCode::Key4) { settings.diagonal = false; }
+    else if keys.just_pressed(KeyCode::Key8) { settings.diagonal = true; }
+    else { changed = false; }
+
+    if changed { message.send(ShowMessage(settings.describe())); }
+}
+
+fn update(
+    tool: Res<SelectedTool>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    selection: Res<Selection>,
+    settings: Res<FillSettings>,
+    mut clicks: EventReader<Clicked>,
+    mut grid: ResMut<Grid>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut filled: Local<bool>,
+) {
+    if tool.0 != Tools::Fill { clicks.clear(); return; }
+
+    for Clicked(id, right_button) in clicks.iter() {
+        // Dragging over other tiles doesn't fill again
+        if *right_button || *filled { continue }
+        let ButtonId::Grid(x, y) = id else { continue };
+        let Some(target) = grid.tiles().get(&(*x, *y)) else { continue };
+        let target = target.clone();
+
+        let region = flood(&grid, (*x, *y), &target, &settings);
+
+        let colors_only = keys.pressed(KeyCode::LShift);
+        for pos in region.iter() {
+            let Some(tile) = grid.tiles_mut().get_mut(pos) else { continue };
+            tile.bg = selection.bg;
+            tile.fg = selection.fg;
+            if !colors_only {
+                tile.index = selection.index;
+                tile.rotation = selection.rotation;
+                tile.flip = selection.flip;
+            }
+        }
+
+        grid_changed.send(GridChanged(region));
+        *filled = true;
+    }
+
+    if !mouse.pressed(MouseButton::Left) { *filled = false; }
+}
+
+fn flood(grid: &Grid, start: (isize, isize), target: &Tile, settings: &FillSettings) -> Vec<(isize, isize)> {
+    let mut neighbours = vec![(1, 0), (-1, 0), (0, 1), (0, -1)];
+    if settings.diagonal { neighbours.extend([(1, 1), (1, -1), (-1, 1), (-1, -1)]); }
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    let mut region = Vec::new();
+    visited.insert(start);
+    queue.push_back(start);
+
+    while let Some((x, y)) = queue.pop_front() {
+        region.push((x, y));
+        for (dx, dy) in neighbours.iter() {
+            let pos = (x + dx, y + dy);
+            if visited.contains(&pos) { continue }
+            let Some(tile) = grid.tiles().get(&pos) else { continue };
+            if !settings.matches(target, tile) { continue }
+            visited.insert(pos);
+            queue.push_back(pos);
+        }
+    }
+
+    region
+}
+
+fn cleanup() {
+
+}
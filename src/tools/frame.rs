@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::{AppState, mouse, util};
+use crate::grid::{Grid, GridChanged, HoveredTile, Tile};
+use crate::loading::Textures;
+use crate::mouse::ButtonId;
+use crate::quick_tiles::Selection;
+use crate::tilesets::{Tileset, Tilesets};
+use crate::toolbar::SelectedTool;
+use crate::tools::shape::{self, Preview};
+use crate::tools::Tools;
+use crate::ui::{Prompt, ShowMessage};
+
+pub(crate) struct FramePlugin;
+
+impl Plugin for FramePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(FrameSettings { style: Style::Single })
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems((settings, update.before(shape::update_preview)).chain().in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(super) enum Style {
+    Single,
+    Double,
+    Rounded,
+    /// Index in [Tileset::frames].
+    Custom(usize),
+}
+
+impl Style {
+    /// Box-drawing characters of the charmap, in the order of [crate::tilesets::Frame::tiles].
+    fn chars(&self) -> &'static str {
+        match self {
+            Style::Single | Style::Custom(_) => "┌─┐│┘─└│",
+            Style::Double => "╔═╗║╝═╚║",
+            Style::Rounded => "╭─╮│╯─╰│",
+        }
+    }
+
+    /// Look-alikes for tilesets without box-drawing glyphs.
+    fn ascii(&self) -> &'static str {
+        match self {
+            Style::Single | Style::Custom(_) => "+-+|+-+|",
+            Style::Double => "#=#|#=#|",
+            Style::Rounded => ".-.|'-'|",
+        }
+    }
+}
+
+#[derive(Resource)]
+struct FrameSettings {
+    style: Style,
+}
+
+impl FrameSettings {
+    fn describe(&self, tileset: &Tileset) -> String {
+        match self.style {
+            Style::Custom(i) => format!("Frame: {}", tileset.frames.get(i).map_or("?", |frame| frame.name.as_str())),
+            style => format!("Frame: {:?}", style),
+        }
+    }
+}
+
+/// Tile index, flip and rotation.
+pub(super) type Part = (usize, (bool, bool), u8);
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 22,
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Frame,
+            shortcut: 'b',
+            priority: util::tool_priority::FRAME,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Frame),
+            hover_click: false,
+        });
+}
+
+/// 1 to 3 pick a single, double or rounded frame, 4 cycles through the frames of the tileset.
+fn settings(
+    tool: Res<SelectedTool>,
+    keys: Res<Input<KeyCode>>,
+    prompt: Option<Res<Prompt>>,
+    tilesets: Res<Tilesets>,
+    mut settings: ResMut<FrameSettings>,
+    mut message: EventWriter<ShowMessage>,
+) {
+    if tool.0 != Tools::Frame || prompt.is_some() { return; }
+    if keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) { return; }
+    let tileset = tilesets.active();
+
+    settings.style = if keys.just_pressed(KeyCode::Key1) { Style::Single }
+    else if keys.just_pressed(KeyCode::Key2) { Style::Double }
+    else if keys.just_pressed(KeyCode::Key3) { Style::Rounded }
+    else if keys.just_pressed(KeyCode::Key4) {
+        if tileset.frames.is_empty() {
+            message.send(ShowMessage(format!("No frames in {}", tileset.name)));
+            return;
+        }
+        match settings.style {
+            Style::Custom(i) => Style::Custom((i + 1) % tileset.frames.len()),
+            _ => Style::Custom(0),
+        }
+    }
+    else { return; };
+
+    message.send(ShowMessage(settings.describe(tileset)));
+}
+
+/// Frame of 8 tiles, or from 2: the top left corner is flipped, the top edge flipped and rotated.
+fn parts(tiles: &[usize]) -> Option<[Part; 8]> {
+    let &[corner, edge] = tiles else {
+        return <[usize; 8]>::try_from(tiles).ok().map(|tiles| tiles.map(|index| (index, (false, false), 0)));
+    };
+    Some([
+        (corner, (false, false), 0),
+        (edge, (false, false), 0),
+        (corner, (true, false), 0),
+        (edge, (false, false), 1),
+        (corner, (true, true), 0),
+        (edge, (false, true), 0),
+        (corner, (false, true), 0),
+        (edge, (false, false), 3),
+    ])
+}
+
+/// Box-drawing glyphs missing from the charmap come from its corner and edge, or from ASCII look-alikes.
+pub(super) fn resolve(style: Style, tileset: &Tileset) -> Option<[Part; 8]> {
+    if let Style::Custom(i) = style {
+        if let Some(frame) = tileset.frames.get(i) { return parts(&frame.tiles); }
+    }
+    let tiles = |chars: &str| chars.chars().map(|c| tileset.charmap.tile(c)).collect::<Option<Vec<usize>>>();
+    let chars = style.chars();
+    tiles(chars)
+        .or_else(|| tiles(&chars.chars().take(2).collect::<String>()))
+        .or_else(|| tiles(style.ascii()))
+        .or_else(|| tiles("+-"))
+        .and_then(|tiles| parts(&tiles))
+}
+
+/// Part of the rectangle edges at a position, none inside. A single row or column is a line.
+pub(super) fn part(a: (isize, isize), b: (isize, isize), (x, y): (isize, isize)) -> Option<usize> {
+    let (x0, x1, y0, y1) = (a.0.min(b.0), a.0.max(b.0), a.1.min(b.1), a.1.max(b.1));
+    // Rows go down the grid
+    match (x == x0, x == x1, y == y0, y == y1) {
+        _ if y0 == y1 => Some(1),
+        _ if x0 == x1 => Some(7),
+        (true, _, true, _) => Some(0),
+        (_, true, true, _) => Some(2),
+        (_, true, _, true) => Some(4),
+        (true, _, _, true) => Some(6),
+        (_, _, true, _) => Some(1),
+        (_, true, _, _) => Some(3),
+        (_, _, _, true) => Some(5),
+        (true, _, _, _) => Some(7),
+        _ => None,
+    }
+}
+
+/// Parts of the edges of a rectangle.
+fn frame(a: (isize, isize), b: (isize, isize), parts: &[Part; 8]) -> Vec<((isize, isize), Part)> {
+    let (x0, x1, y0, y1) = (a.0.min(b.0), a.0.max(b.0), a.1.min(b.1), a.1.max(b.1));
+    (y0..=y1)
+        .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+        .filter_map(|pos| part(a, b, pos).map(|i| (pos, parts[i])))
+        .collect()
+}
+
+/// Dragging previews the frame, releasing the button draws it.
+fn update(
+    mut dragged: Local<Option<((isize, isize), (isize, isize))>>,
+    mut preview: ResMut<Preview>,
+    mut grid: ResMut<Grid>,
+    mut grid_changed: EventWriter<GridChanged>,
+    tool: Res<SelectedTool>,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    hovered: Res<HoveredTile>,
+    selection: Res<Selection>,
+    settings: Res<FrameSettings>,
+    tilesets: Res<Tilesets>,
+) {
+    if tool.0 != Tools::Frame {
+        if dragged.take().is_some() { preview.0.clear(); }
+        return;
+    }
+
+    let Some((start, mut end)) = shape::drag(&mut dragged, &mouse, &hovered) else { return };
+    if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) { end = shape::square(start, end); }
+    let Some(parts) = resolve(settings.style, tilesets.active()) else { return };
+    preview.0 = frame(start, end, &parts).into_iter()
+        .filter(|(pos, _)| grid.contains(*pos))
+        .map(|(pos, (index, flip, rotation))| (pos, Tile { bg: selection.bg, fg: selection.fg, index, flip, rotation }))
+        .collect();
+
+    if !mouse.pressed(MouseButton::Left) {
+        shape::draw(&mut preview, &mut grid, &mut grid_changed);
+        *dragged = None;
+    }
+}
+
+fn cleanup() {
+
+}
@@ -1,15 +1,21 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
 
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
-use strum::IntoEnumIterator;
 
-use crate::{AppState, grid, mouse, util};
-use crate::grid::{Grid, GridResized, Tile, Zoom};
+use crate::{ansi, AppState, mouse, util};
+use crate::convert::Format;
+use crate::grid::{Grid, GridChanged, GridResized, Tile};
 use crate::loading::Textures;
 use crate::mouse::{ButtonId, Clicked};
+use crate::palette::TilePalette;
+use crate::project::{self, Document};
+use crate::tilesets::Tilesets;
 use crate::tools::Tools;
-use crate::util::{Palette, TILE};
+use crate::ui::{OpenPrompt, PromptKind, PromptSubmitted, ShowMessage};
 
 pub(crate) struct ImportPlugin;
 
@@ -17,7 +23,7 @@ impl Plugin for ImportPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
-            .add_system(update.in_set(OnUpdate(AppState::Editor)))
+            .add_systems((update, on_prompt).in_set(OnUpdate(AppState::Editor)))
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
     }
 }
@@ -41,7 +47,7 @@ fn setup(
         ))
         .insert(crate::toolbar::Tool {
             id: Tools::Import,
-            shortcut: 'i',
+            shortcut: 'm',
             priority: util::tool_priority::IMPORT,
         })
         .insert(mouse::Clickable {
@@ -52,82 +58,369 @@ fn setup(
         });
 }
 
+/// Replaces the active layer, other layers are cropped or extended to the new size.
+fn replace(
+    grid: &mut Grid,
+    w: usize,
+    h: usize,
+    tiles: HashMap<(isize, isize), Tile>,
+    grid_changed: &mut EventWriter<GridChanged>,
+    grid_resized: &mut EventWriter<GridResized>,
+) {
+    grid.tiles_mut().clear();
+    grid.x0 = 0;
+    grid.y0 = 0;
+    grid.w = w;
+    grid.h = h;
+    grid_changed.send(GridChanged(tiles.keys().copied().collect()));
+    grid.tiles_mut().extend(tiles);
+    grid_resized.send(GridResized);
+}
+
+/// Left click imports a tile list from the clipboard, right click opens ANSI art, text or a tile list file.
 fn update(
-    mut commands: Commands,
     mut clicked: EventReader<Clicked>,
     mut grid: Option<ResMut<Grid>>,
+    mut grid_changed: EventWriter<GridChanged>,
     mut grid_resized: EventWriter<GridResized>,
-    textures: Res<Textures>,
-    zoom: Res<Zoom>,
+    mut message: EventWriter<ShowMessage>,
+    mut prompt: EventWriter<OpenPrompt>,
+    document: Res<Document>,
+    tilesets: Res<Tilesets>,
+    palette: Res<TilePalette>,
 ) {
     let Some(mut grid) = grid else { return; };
     for Clicked(id, right) in clicked.iter() {
-        if *right { continue }
         let ButtonId::Tool(Tools::Import) = id else { continue };
+        if *right {
+            prompt.send(OpenPrompt {
+                kind: PromptKind::Import,
+                label: "Import:".to_string(),
+                default: document.path.as_ref()
+                    .map(|p| p.with_extension("ans").to_string_lossy().to_string())
+                    .unwrap_or(util::misc::DEFAULT_ANS.to_string()),
+            });
+            continue;
+        }
 
-        let Ok(clipboard) = cli_clipboard::get_contents() else { continue };
+        let Ok(clipboard) = cli_clipboard::get_contents() else {
+            message.send(ShowMessage("Couldn't read the clipboard".to_string()));
+            continue;
+        };
 
-        grid.tiles.values().for_each(|(_, id)| commands.entity(*id).despawn_recursive());
-        grid.tiles.clear();
+        // Keep the current drawing if anything is wrong
+        let tiles = match parse_tuples(&clipboard, tilesets.active().count(), palette.tuple_count()) {
+            Ok(tiles) => tiles,
+            Err(e) => {
+                message.send(ShowMessage(format!("Import error {}", e)));
+                continue;
+            }
+        };
 
-        let palette = Palette::iter().collect::<Vec<Palette>>();
+        let Some((w, h, new_tiles)) = tuple_tiles(tiles, &palette) else { continue };
+        replace(&mut grid, w, h, new_tiles, &mut grid_changed, &mut grid_resized);
+    }
+}
 
-        let mut new_tiles = HashMap::new();
+/// Size and tiles of a tile list, `y` goes up from the bottom row.
+fn tuple_tiles(tiles: Vec<util::TILE>, palette: &TilePalette) -> Option<(usize, usize, HashMap<(isize, isize), Tile>)> {
+    let max_x = tiles.iter().map(|t| t.0).max()?;
+    let max_y = tiles.iter().map(|t| t.1).max()?;
 
-        let tiles: Vec<TILE> = clipboard.lines().filter_map(|line| parse_tile(line)).collect::<Vec<TILE>>();
-        let Some(max_x) = tiles.iter().map(|t| t.0).max() else { continue };
-        let Some(max_y) = tiles.iter().map(|t| t.1).max() else { continue };
+    let mut new_tiles = HashMap::new();
+    for tile in tiles {
+        let t = Tile {
+            bg: palette.from_tuple(tile.3),
+            fg: palette.from_tuple(tile.4),
+            index: tile.2,
+            flip: (tile.5, tile.7),
+            rotation: tile.6,
+        };
 
-        for tile in tiles {
-            let t = Tile {
-                bg: palette[tile.3],
-                fg: palette[tile.4],
-                index: tile.2,
-                flip: (tile.5, false),
-                rotation: tile.6,
-            };
+        new_tiles.insert((tile.0 as isize, max_y as isize - tile.1 as isize), t);
+    }
+    Some((max_x + 1, max_y + 1, new_tiles))
+}
 
-            let id = grid::spawn_tile(
-                &mut commands,
-                tile.0 as isize, max_y as isize - tile.1 as isize,
-                &t, &grid, &textures, &zoom
-            );
+/// `.ron` and `.tuples` files are read as tile lists, `.ans` files as code page 437,
+/// other files too unless they are UTF-8.
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut grid: Option<ResMut<Grid>>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut grid_resized: EventWriter<GridResized>,
+    mut message: EventWriter<ShowMessage>,
+    tilesets: Res<Tilesets>,
+    palette: Res<TilePalette>,
+) {
+    let Some(grid) = grid.as_mut() else { return; };
+    for PromptSubmitted(kind, path) in submitted.iter() {
+        if *kind != PromptKind::Import || path.is_empty() { continue }
 
-            new_tiles.insert((tile.0 as isize, tile.1 as isize), (t, id));
-            // println!("{} {} {} {} {} {} {}", tile.0, tile.1, tile.2, tile.3, tile.4, tile.5, tile.6);
+        let path = Path::new(path);
+        let format = Format::from_path(path);
+        let art = fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| match format {
+                Some(Format::Tuples) => {
+                    let text = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+                    let tiles = parse_tuples(&text, tilesets.active().count(), palette.tuple_count()).map_err(|e| e.to_string())?;
+                    tuple_tiles(tiles, &palette).ok_or("no tiles".to_string())
+                }
+                _ => ansi::from_ansi(&bytes, format == Some(Format::Ans), &palette, tilesets.active())
+                    .map(|(w, h, tiles)| (w, h, tiles.into_iter().collect())),
+            });
+        match art {
+            Ok((w, h, tiles)) => {
+                replace(grid, w, h, tiles, &mut grid_changed, &mut grid_resized);
+                message.send(ShowMessage(format!("Imported {} ({}x{})", project::file_name(path), w, h)));
+            }
+            Err(e) => message.send(ShowMessage(format!("Couldn't import: {}", e))),
         }
+    }
+}
 
-        grid.x0 = 0;
-        grid.y0 = 0;
-        grid.w = max_x as usize + 1;
-        grid.h = max_y as usize + 1;
+/// Error in an imported tile list, `line` and `column` start at 1.
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
 
-        for ((x, y), (t, e)) in new_tiles.iter() {
-            grid.tiles.insert((*x, max_y as isize - *y), (t.to_owned(), *e));
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    OpenList,
+    CloseList,
+    Open,
+    Close,
+    Comma,
+    Semicolon,
+    Number(u64),
+    Bool(bool),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::OpenList => write!(f, "`[`"),
+            Token::CloseList => write!(f, "`]`"),
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Semicolon => write!(f, "`;`"),
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::Bool(b) => write!(f, "`{}`", b),
         }
+    }
+}
+
+/// Tokens with their line and column, `//` and `/* */` comments are skipped.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize, usize)>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let (mut line, mut column) = (1, 1);
 
-        grid_resized.send(GridResized);
+    while let Some(c) = chars.next() {
+        let (l, col) = (line, column);
+        let error = |message: String| ParseError { line: l, column: col, message };
+        if c == '\n' { line += 1; column = 1; } else { column += 1; }
+
+        let token = match c {
+            '[' => Token::OpenList,
+            ']' => Token::CloseList,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            ';' => Token::Semicolon,
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                column += 1;
+                let mut last = ' ';
+                loop {
+                    let Some(c) = chars.next() else { return Err(error("unclosed comment".to_string())) };
+                    if c == '\n' { line += 1; column = 1; } else { column += 1; }
+                    if last == '*' && c == '/' { break }
+                    last = c;
+                }
+                continue;
+            }
+            c if c.is_ascii_alphanumeric() => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    word.push(c);
+                    column += 1;
+                }
+                match word.as_str() {
+                    "true" => Token::Bool(true),
+                    "false" => Token::Bool(false),
+                    _ => Token::Number(word.parse::<u64>().map_err(|_| error(format!("unexpected `{}`", word)))?),
+                }
+            }
+            c => return Err(error(format!("unexpected `{}`", c))),
+        };
+        tokens.push((token, l, col));
     }
+
+    Ok(tokens)
 }
 
-fn parse_tile(line: &str) -> Option<util::TILE> {
-    let mut line = line.trim().strip_prefix("(")?;
-    line = line.strip_suffix("),")?;
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    next: usize,
+    end: (usize, usize),
+}
 
-    let split = line.split(", ").collect::<Vec<&str>>();
-    if split.len() != 7 { return None; }
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(t, _, _)| t)
+    }
 
-    let x = split[0].parse::<util::X>().ok()?;
-    let y = split[1].parse::<util::Y>().ok()?;
-    let index = split[2].parse::<util::INDEX>().ok()?;
-    let bg = split[3].parse::<util::BG>().ok()?;
-    let fg = split[4].parse::<util::FG>().ok()?;
-    let flip = split[5].parse::<util::FLIP>().ok()?;
-    let rotation = split[6].parse::<util::ROTATION>().ok()?;
+    fn error(&self, message: String) -> ParseError {
+        let (line, column) = self.tokens.get(self.next).map(|(_, l, c)| (*l, *c)).unwrap_or(self.end);
+        ParseError { line, column, message }
+    }
 
-    return Some((x, y, index, bg, fg, flip, rotation));
+    fn found(&self) -> String {
+        self.peek().map(|t| t.to_string()).unwrap_or("end of input".to_string())
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        if self.peek() != Some(&token) { return false; }
+        self.next += 1;
+        true
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ParseError> {
+        if self.eat(token.clone()) { return Ok(()); }
+        Err(self.error(format!("expected {}, found {}", token, self.found())))
+    }
+
+    fn number(&mut self, what: &str, max: u64) -> Result<u64, ParseError> {
+        let Some(Token::Number(n)) = self.peek().cloned() else {
+            return Err(self.error(format!("expected {}, found {}", what, self.found())));
+        };
+        if n >= max { return Err(self.error(format!("{} {} should be less than {}", what, n, max))); }
+        self.next += 1;
+        Ok(n)
+    }
+
+    fn bool(&mut self, what: &str) -> Result<bool, ParseError> {
+        let Some(Token::Bool(b)) = self.peek().cloned() else {
+            return Err(self.error(format!("expected {}, found {}", what, self.found())));
+        };
+        self.next += 1;
+        Ok(b)
+    }
+
+    /// `(x, y, index, bg, fg, flip_x, rotation[, flip_y])` with an optional trailing comma.
+    fn tile(&mut self, tiles: u64, colors: u64) -> Result<util::TILE, ParseError> {
+        // Imports resize the grid to fit
+        let coordinate = util::size::MAX_GRID as u64;
+
+        self.expect(Token::Open)?;
+        let x = self.number("x", coordinate)? as util::X;
+        self.expect(Token::Comma)?;
+        let y = self.number("y", coordinate)? as util::Y;
+        self.expect(Token::Comma)?;
+        let index = self.number("tile index", tiles)? as util::INDEX;
+        self.expect(Token::Comma)?;
+        let bg = self.number("background color", colors)? as util::BG;
+        self.expect(Token::Comma)?;
+        let fg = self.number("foreground color", colors)? as util::FG;
+        self.expect(Token::Comma)?;
+        let flip = self.bool("horizontal flip")?;
+        self.expect(Token::Comma)?;
+        let rotation = self.number("rotation", 4)? as util::ROTATION;
+        let mut flip_y = false;
+        if self.eat(Token::Comma) && self.peek() != Some(&Token::Close) {
+            flip_y = self.bool("vertical flip")?;
+            self.eat(Token::Comma);
+        }
+        self.expect(Token::Close)?;
+
+        Ok((x, y, index, bg, fg, flip, rotation, flip_y))
+    }
+}
+
+/// Parses tiles in the export format, the surrounding `[` `];` are optional
+/// and so is the comma after the last tile. Tile and color indices are below the counts.
+pub(crate) fn parse_tuples(text: &str, tile_count: usize, color_count: usize) -> Result<Vec<util::TILE>, ParseError> {
+    let end = (text.lines().count().max(1), text.lines().last().map(|l| l.chars().count()).unwrap_or(0) + 1);
+    let mut parser = Parser { tokens: tokenize(text)?, next: 0, end };
+
+    let list = parser.eat(Token::OpenList);
+    let mut tiles = vec![];
+    while parser.peek() == Some(&Token::Open) {
+        tiles.push(parser.tile(tile_count as u64, color_count as u64)?);
+        if parser.peek() == Some(&Token::Open) { parser.expect(Token::Comma)?; }
+        if !parser.eat(Token::Comma) { break }
+    }
+    if list { parser.expect(Token::CloseList)?; }
+    parser.eat(Token::Semicolon);
+
+    if parser.peek().is_some() {
+        return Err(parser.error(format!("expected a tile, found {}", parser.found())));
+    }
+    if tiles.is_empty() {
+        return Err(parser.error("no tiles".to_string()));
+    }
+
+    Ok(tiles)
 }
 
 fn cleanup() {
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        parse_tuples(text, 10, 4).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn tiles_with_optional_delimiters() {
+        let tiles = parse_tuples("[(0, 1, 2, 3, 0, false, 1, true), // first\n/* second */ (1,0,9,0,3,true,0,),];", 10, 4).unwrap();
+        assert_eq!(tiles, vec![(0, 1, 2, 3, 0, false, 1, true), (1, 0, 9, 0, 3, true, 0, false)]);
+        assert_eq!(parse_tuples("(5, 6, 0, 0, 0, false, 3)", 10, 4).unwrap(), vec![(5, 6, 0, 0, 0, false, 3, false)]);
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(error(""), "1:1: no tiles");
+        assert_eq!(error("[]"), "1:3: no tiles");
+        assert_eq!(error("(0, 0, 0, 0, 0, false, 0) (1, 0, 0, 0, 0, false, 0)"), "1:27: expected `,`, found `(`");
+        assert_eq!(error("[(0, 0, 0, 0, 0, false, 0)"), "1:27: expected `]`, found end of input");
+        assert_eq!(error("(0, 0, 0, 0, 0, 1, 0)"), "1:17: expected horizontal flip, found `1`");
+        assert_eq!(error("(0, 0,\n0, 0 0, false, 0)"), "2:6: expected `,`, found `0`");
+        assert_eq!(error("(0, -1, 0, 0, 0, false, 0)"), "1:5: unexpected `-`");
+        assert_eq!(error("(0, 0x1, 0, 0, 0, false, 0)"), "1:5: unexpected `0x1`");
+        assert_eq!(error("/* (0, 0, 0, 0, 0, false, 0)"), "1:1: unclosed comment");
+        assert_eq!(error("(0, 0, 0, 0, 0, false, 0); x"), "1:28: unexpected `x`");
+    }
+
+    #[test]
+    fn out_of_range_input() {
+        let max = util::size::MAX_GRID;
+        assert!(parse_tuples(&format!("({}, {}, 0, 0, 0, false, 0)", max - 1, max - 1), 10, 4).is_ok());
+        assert_eq!(error(&format!("({}, 0, 0, 0, 0, false, 0)", max)), format!("1:2: x {} should be less than {}", max, max));
+        assert_eq!(error("(0, 0, 10, 0, 0, false, 0)"), "1:8: tile index 10 should be less than 10");
+        assert_eq!(error("(0, 0, 0, 4, 0, false, 0)"), "1:11: background color 4 should be less than 4");
+        assert_eq!(error("(0, 0, 0, 0, 0, false, 4)"), "1:24: rotation 4 should be less than 4");
+        assert_eq!(error("(0, 18446744073709551616, 0, 0, 0, false, 0)"), "1:5: unexpected `18446744073709551616`");
+    }
+}
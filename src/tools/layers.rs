@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::{AppState, mouse, util};
+use crate::grid::{Grid, GridChanged, LayersChanged};
+use crate::loading::Textures;
+use crate::mouse::{ButtonId, Clicked};
+use crate::tools::{TextCursorState, Tools};
+use crate::ui::{OpenPrompt, Prompt, PromptKind, PromptSubmitted, ShowMessage};
+
+pub(crate) struct LayersPlugin;
+
+impl Plugin for LayersPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<EditLayers>()
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems((update, on_keys, on_prompt, apply).chain().in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+enum EditLayers {
+    /// Activates the layer above (1) or below (-1), wrapping around.
+    Select(isize),
+    /// Moves the active layer up or down the stack.
+    Move(isize),
+    Add,
+    Remove,
+    MergeDown,
+    ToggleVisible,
+    Alpha(f32),
+    Rename(String),
+}
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 16,
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Layers,
+            shortcut: 'l',
+            priority: util::tool_priority::LAYERS,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Layers),
+            hover_click: false,
+        });
+}
+
+/// Left click activates the next layer, right click adds one.
+fn update(
+    mut clicked: EventReader<Clicked>,
+    mut edit: EventWriter<EditLayers>,
+) {
+    for Clicked(id, right) in clicked.iter() {
+        let ButtonId::Tool(Tools::Layers) = id else { continue };
+        edit.send(if *right { EditLayers::Add } else { EditLayers::Select(1) });
+    }
+}
+
+/// Alt +
+/// - `[` / `]`: active layer below / above, with shift: move the layer down / up
+/// - `N`: new layer, `D`: delete layer, `M`: merge down
+/// - `H`: hide / show, `-` / `=`: less / more opaque, `R`: rename
+fn on_keys(
+    keys: Res<Input<KeyCode>>,
+    grid: Option<Res<Grid>>,
+    prompt: Option<Res<Prompt>>,
+    cursor: Option<Res<TextCursorState>>,
+    mut edit: EventWriter<EditLayers>,
+    mut open_prompt: EventWriter<OpenPrompt>,
+) {
+    let Some(grid) = grid else { return };
+    if prompt.is_some() || cursor.is_some() { return; }
+    if !keys.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) { return; }
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    for key in keys.get_just_pressed() {
+        edit.send(match key {
+            KeyCode::LBracket if shift => EditLayers::Move(-1),
+            KeyCode::RBracket if shift => EditLayers::Move(1),
+            KeyCode::LBracket => EditLayers::Select(-1),
+            KeyCode::RBracket => EditLayers::Select(1),
+            KeyCode::N => EditLayers::Add,
+            KeyCode::D => EditLayers::Remove,
+            KeyCode::M => EditLayers::MergeDown,
+            KeyCode::H => EditLayers::ToggleVisible,
+            KeyCode::Minus => EditLayers::Alpha(-0.1),
+            KeyCode::Equals => EditLayers::Alpha(0.1),
+            KeyCode::R => {
+                open_prompt.send(OpenPrompt {
+                    kind: PromptKind::RenameLayer,
+                    label: "Layer name:".to_string(),
+                    default: grid.layers[grid.active].name.clone(),
+                });
+                continue;
+            }
+            _ => continue,
+        });
+    }
+}
+
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut edit: EventWriter<EditLayers>,
+) {
+    for PromptSubmitted(kind, name) in submitted.iter() {
+        if *kind != PromptKind::RenameLayer || name.is_empty() { continue }
+        edit.send(EditLayers::Rename(name.clone()));
+    }
+}
+
+fn describe(grid: &Grid) -> String {
+    let layer = &grid.layers[grid.active];
+    let mut text = format!("Layer {}/{}: {}", grid.active + 1, grid.layers.len(), layer.name);
+    if layer.alpha < 1. { text += &format!(" {}%", (layer.alpha * 100.).round()); }
+    if !layer.visible { text += " (hidden)"; }
+    text
+}
+
+fn apply(
+    mut commands: Commands,
+    mut ev: EventReader<EditLayers>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut layers_changed: EventWriter<LayersChanged>,
+    mut message: EventWriter<ShowMessage>,
+    grid: Option<ResMut<Grid>>,
+) {
+    let Some(mut grid) = grid else { return };
+    for edit in ev.iter() {
+        let count = grid.layers.len();
+        let active = grid.active;
+
+        match edit {
+            EditLayers::Select(d) => {
+                grid.active = (active as isize + d).rem_euclid(count as isize) as usize;
+            }
+            EditLayers::Move(d) => {
+                let target = active as isize + d;
+                if target < 0 || target >= count as isize { continue }
+                grid.layers.swap(active, target as usize);
+                grid.active = target as usize;
+            }
+            EditLayers::Add => {
+                if count >= util::misc::MAX_LAYERS {
+                    message.send(ShowMessage(format!("Can't have more than {} layers", util::misc::MAX_LAYERS)));
+                    continue;
+                }
+                let layer = grid.new_layer(&format!("Layer {}", count + 1));
+                grid.layers.insert(active + 1, layer);
+                grid.active = active + 1;
+            }
+            EditLayers::Remove => {
+                if count == 1 {
+                    message.send(ShowMessage("Can't remove the last layer".to_string()));
+                    continue;
+                }
+                let layer = grid.layers.remove(active);
+                Grid::despawn_layer(&layer, &mut commands);
+                grid.active = active.min(count - 2);
+            }
+            EditLayers::MergeDown => {
+                if active == 0 {
+                    message.send(ShowMessage("No layer below".to_string()));
+                    continue;
+                }
+                let upper = grid.layers.remove(active);
+                let below = &mut grid.layers[active - 1];
+                let mut positions = Vec::new();
+                Grid::despawn_layer(&upper, &mut commands);
+                for (pos, tile) in upper.tiles {
+                    let Some(t) = below.tiles.get_mut(&pos) else { continue };
+                    *t = tile.over(t);
+                    positions.push(pos);
+                }
+                grid.active = active - 1;
+                grid_changed.send(GridChanged(positions));
+            }
+            EditLayers::ToggleVisible => {
+                let layer = &mut grid.layers[active];
+                layer.visible = !layer.visible;
+            }
+            EditLayers::Alpha(d) => {
+                let layer = &mut grid.layers[active];
+                layer.alpha = ((layer.alpha + d) * 10.).round().clamp(0., 10.) / 10.;
+            }
+            EditLayers::Rename(name) => {
+                grid.layers[active].name = name.clone();
+            }
+        }
+
+        layers_changed.send(LayersChanged);
+        message.send(ShowMessage(describe(&grid)));
+    }
+}
+
+fn cleanup() {
+
+}
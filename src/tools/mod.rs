@@ -1,12 +1,24 @@
 use bevy::prelude::*;
 
+pub(crate) use export::to_tuples;
+pub(crate) use import::parse_tuples;
+pub(crate) use text::TextCursorState;
+
 mod pencil;
+mod shape;
+mod frame;
+mod fill;
 mod eraser;
+mod select;
 mod pick;
 mod text;
 mod resize;
 mod export;
 mod import;
+mod layers;
+mod tileset;
+mod open;
+mod save;
 
 pub struct ToolsPlugin;
 
@@ -15,12 +27,20 @@ impl Plugin for ToolsPlugin {
         app
             .add_event::<ActivateTool>()
             .add_plugin(pencil::PencilPlugin)
+            .add_plugin(shape::ShapePlugin)
+            .add_plugin(frame::FramePlugin)
+            .add_plugin(fill::FillPlugin)
             .add_plugin(eraser::EraserPlugin)
+            .add_plugin(select::SelectPlugin)
             .add_plugin(text::TextPlugin)
             .add_plugin(resize::ResizePlugin)
             .add_plugin(export::ExportPlugin)
             .add_plugin(import::ImportPlugin)
-            // .add_plugin(pick::PickPlugin)
+            .add_plugin(open::OpenPlugin)
+            .add_plugin(save::SavePlugin)
+            .add_plugin(pick::PickPlugin)
+            .add_plugin(layers::LayersPlugin)
+            .add_plugin(tileset::TilesetPlugin)
         ;
     }
 }
@@ -28,12 +48,20 @@ impl Plugin for ToolsPlugin {
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum Tools {
     Pencil,
+    Shape,
+    Frame,
+    Fill,
     Eraser,
+    Select,
     Pick,
     Text,
     Resize,
+    Layers,
+    Tileset,
     Export,
     Import,
+    Open,
+    Save,
     Custom(&'static str),
     CustomNonSelectable(&'static str),
 }
@@ -42,10 +70,18 @@ impl Tools {
     pub fn is_selectable(&self) -> bool {
         match self {
             Tools::Export | Tools::Import
+            | Tools::Open | Tools::Save | Tools::Layers | Tools::Tileset
             | Tools::CustomNonSelectable(_) => false,
             _ => true,
         }
     }
+
+    pub fn name(&self) -> String {
+        match self {
+            Tools::Custom(name) | Tools::CustomNonSelectable(name) => name.to_string(),
+            _ => format!("{:?}", self),
+        }
+    }
 }
 
-pub struct ActivateTool(Tools);
\ No newline at end of file
+pub struct ActivateTool(pub Tools);
\ No newline at end of file
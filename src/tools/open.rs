@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::{AppState, mouse, util};
+use crate::loading::Textures;
+use crate::mouse::{ButtonId, Clicked};
+use crate::project::{self, Document, OpenProject};
+use crate::tools::Tools;
+use crate::ui::{OpenPrompt, PromptKind, PromptSubmitted};
+
+pub(crate) struct OpenPlugin;
+
+impl Plugin for OpenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems((update, on_prompt).in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 14,
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Open,
+            shortcut: 'o',
+            priority: util::tool_priority::OPEN,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Open),
+            hover_click: false,
+        });
+}
+
+fn update(
+    mut clicked: EventReader<Clicked>,
+    mut prompt: EventWriter<OpenPrompt>,
+    document: Res<Document>,
+) {
+    for Clicked(id, _) in clicked.iter() {
+        let ButtonId::Tool(Tools::Open) = id else { continue };
+
+        prompt.send(OpenPrompt {
+            kind: PromptKind::Open,
+            label: "Open:".to_string(),
+            default: document.path.as_ref()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or(util::misc::DEFAULT_PROJECT.to_string()),
+        });
+    }
+}
+
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut open: EventWriter<OpenProject>,
+) {
+    for PromptSubmitted(kind, path) in submitted.iter() {
+        if *kind != PromptKind::Open || path.is_empty() { continue }
+        open.send(OpenProject(project::with_extension(path)));
+    }
+}
+
+fn cleanup() {
+
+}
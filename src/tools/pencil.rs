@@ -66,7 +66,7 @@ fn update(
     if tool.0 != Tools::Pencil { clicks.clear(); return; }
     for Clicked(id, right_button) in clicks.iter() {
         if let ButtonId::Grid(x, y) = id {
-            let Some((ref mut tile, _)) = grid.tiles.get_mut(&(*x, *y)) else { continue };
+            let Some(tile) = grid.tiles_mut().get_mut(&(*x, *y)) else { continue };
 
             if *right_button {
                 // Tile info -> Selection
@@ -78,7 +78,7 @@ fn update(
                     selection.index = tile.index;
                     ev_tile.send(SelectTile(tile.index));
                     selection.rotation = tile.rotation;
-                    selection.flip = tile.flip.0;
+                    selection.flip = tile.flip;
                 }
             } else {
                 // Selection -> Tile info
@@ -87,7 +87,7 @@ fn update(
                 if !keys.pressed(KeyCode::LShift) {
                     tile.index = selection.index;
                     tile.rotation = selection.rotation;
-                    tile.flip.0 = selection.flip;
+                    tile.flip = selection.flip;
                 }
 
                 grid_changed.send(GridChanged(vec![(*x, *y)]));
@@ -2,17 +2,23 @@ use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
 use crate::{AppState, mouse, util};
+use crate::grid::Grid;
 use crate::loading::Textures;
-use crate::mouse::ButtonId;
+use crate::mouse::{ButtonId, Clicked};
+use crate::quick_tiles::{SelectColor, Selection, SelectTile};
+use crate::toolbar::{SelectedTool, UpdateToolbar};
 use crate::tools::Tools;
+use crate::ui::ShowMessage;
 
 pub(crate) struct PickPlugin;
 
 impl Plugin for PickPlugin {
     fn build(&self, app: &mut App) {
         app
+            .insert_resource(PickSettings { return_to_previous: false })
+            .insert_resource(PreviousTool(Tools::Pencil))
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
-            .add_system(update.in_set(OnUpdate(AppState::Editor)))
+            .add_systems((on_tool_change, update, settings).in_set(OnUpdate(AppState::Editor)))
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
     }
 }
@@ -20,37 +26,122 @@ impl Plugin for PickPlugin {
 #[derive(Component)]
 struct PickUI;
 
+#[derive(Resource)]
+pub struct PickSettings {
+    pub return_to_previous: bool,
+}
+
+#[derive(Resource)]
+struct PreviousTool(Tools);
+
 fn setup(
     mut commands: Commands,
     textures: Res<Textures>,
 ) {
     commands
-        .spawn((
-            SpriteSheetBundle {
-                sprite: TextureAtlasSprite {
-                    index: 4,
-                    anchor: Anchor::BottomLeft,
-                    ..Default::default()
-                },
-                texture_atlas: textures.icons.clone(),
-                transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 15,
+                anchor: Anchor::BottomLeft,
                 ..Default::default()
-            }
-        ))
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
         .insert(crate::toolbar::Tool {
-            id: Tools::Eraser,
+            id: Tools::Pick,
             shortcut: 'i',
             priority: util::tool_priority::PICK,
         })
         .insert(mouse::Clickable {
             w: 16.0,
             h: 16.0,
-            id: ButtonId::Tool(Tools::Eraser),
+            id: ButtonId::Tool(Tools::Pick),
             hover_click: false,
         });
 }
 
-fn update() {}
+fn on_tool_change(
+    mut ev: EventReader<UpdateToolbar>,
+    mut previous: ResMut<PreviousTool>,
+    tool: Res<SelectedTool>,
+) {
+    for UpdateToolbar in ev.iter() {
+        if tool.0 != Tools::Pick { previous.0 = tool.0; }
+    }
+}
+
+/// Right clicking the toolbar icon toggles going back to the previous tool after a pick.
+fn settings(
+    mut clicked: EventReader<Clicked>,
+    mut settings: ResMut<PickSettings>,
+    mut message: EventWriter<ShowMessage>,
+) {
+    for Clicked(id, right) in clicked.iter() {
+        if !*right { continue }
+        let ButtonId::Tool(Tools::Pick) = id else { continue };
+
+        settings.return_to_previous = !settings.return_to_previous;
+        message.send(ShowMessage(if settings.return_to_previous {
+            "Pick: back to previous tool".to_string()
+        } else {
+            "Pick: keep picking".to_string()
+        }));
+    }
+}
+
+/// - left click: tile and colors
+/// - right click: tile only
+/// - shift + left click: foreground only
+/// - shift + right click: background only
+fn update(
+    keys: Res<Input<KeyCode>>,
+    grid: Res<Grid>,
+    settings: Res<PickSettings>,
+    previous: Res<PreviousTool>,
+    mut clicks: EventReader<Clicked>,
+    mut tool: ResMut<SelectedTool>,
+    mut selection: ResMut<Selection>,
+    mut ev_tile: EventWriter<SelectTile>,
+    mut ev_color: EventWriter<SelectColor>,
+    mut update_toolbar: EventWriter<UpdateToolbar>,
+) {
+    if tool.0 != Tools::Pick { clicks.clear(); return; }
+    for Clicked(id, right_button) in clicks.iter() {
+        let ButtonId::Grid(x, y) = id else { continue };
+        let Some(tile) = grid.tiles().get(&(*x, *y)) else { continue };
+
+        let shift = keys.pressed(KeyCode::LShift);
+        let (glyph, fg, bg) = match (shift, *right_button) {
+            (false, false) => (true, true, true),
+            (false, true) => (true, false, false),
+            (true, false) => (false, true, false),
+            (true, true) => (false, false, true),
+        };
+
+        if glyph {
+            selection.index = tile.index;
+            selection.rotation = tile.rotation;
+            selection.flip = tile.flip;
+            ev_tile.send(SelectTile(tile.index));
+        }
+        if fg {
+            selection.fg = tile.fg;
+            ev_color.send(SelectColor(tile.fg, false));
+        }
+        if bg {
+            selection.bg = tile.bg;
+            ev_color.send(SelectColor(tile.bg, true));
+        }
+
+        if settings.return_to_previous {
+            tool.0 = previous.0;
+            update_toolbar.send(UpdateToolbar);
+            break;
+        }
+    }
+}
 
 fn cleanup(
     mut commands: Commands,
@@ -59,4 +150,4 @@ fn cleanup(
     for e in query.iter() {
         commands.entity(e).despawn_recursive();
     }
-}
\ No newline at end of file
+}
@@ -6,8 +6,10 @@ use crate::{AppState, mouse, util};
 use crate::grid::{Grid, GridResized};
 use crate::loading::Textures;
 use crate::mouse::ButtonId;
+use crate::project::NewProject;
 use crate::toolbar::SelectedTool;
-use crate::tools::Tools;
+use crate::tools::{TextCursorState, Tools};
+use crate::ui::{OpenPrompt, Prompt, PromptKind, PromptSubmitted, ShowMessage};
 
 pub(crate) struct ResizePlugin;
 
@@ -15,7 +17,7 @@ impl Plugin for ResizePlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
-            .add_system(update.in_set(OnUpdate(AppState::Editor)))
+            .add_systems((update, on_keys, on_prompt).in_set(OnUpdate(AppState::Editor)))
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
     }
 }
@@ -56,9 +58,11 @@ fn update(
     grid: Option<ResMut<Grid>>,
     mut sprite: Query<(&crate::toolbar::Tool, &mut TextureAtlasSprite)>,
     mut resize_grid: EventWriter<GridResized>,
+    prompt: Option<Res<Prompt>>,
 ) {
-    if tool.0 != Tools::Resize { return; }
+    if tool.0 != Tools::Resize || prompt.is_some() { return; }
     let Some(mut grid) = grid else { return; };
+    let max = util::size::MAX_GRID;
 
     let mut resized = true;
     if keys.pressed(KeyCode::LShift) {
@@ -95,18 +99,18 @@ fn update(
 
         // Extend grid
         if keys.just_pressed(KeyCode::Left) {
-            if grid.w == 24 { return; }
+            if grid.w == max { return; }
             grid.x0 -= 1;
             grid.w += 1;
         } else if keys.just_pressed(KeyCode::Right) {
-            if grid.w == 24 { return; }
+            if grid.w == max { return; }
             grid.w += 1;
         } else if keys.just_pressed(KeyCode::Up) {
-            if grid.h == 24 { return; }
+            if grid.h == max { return; }
             grid.y0 -= 1;
             grid.h += 1;
         } else if keys.just_pressed(KeyCode::Down) {
-            if grid.h == 24 { return; }
+            if grid.h == max { return; }
             grid.h += 1;
         } else {
             resized = false;
@@ -118,6 +122,107 @@ fn update(
     }
 }
 
+/// `WxH` or the name of one of the [util::misc::PRESETS].
+fn parse_size(text: &str) -> Result<(usize, usize), String> {
+    let text = text.to_ascii_lowercase();
+    if let Some((_, w, h)) = util::misc::PRESETS.iter().find(|(name, _, _)| *name == text) {
+        return Ok((*w, *h));
+    }
+
+    let size = text.split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<usize>().ok()?, h.trim().parse::<usize>().ok()?)));
+    match size {
+        Some((w, h)) if (1..=util::size::MAX_GRID).contains(&w) && (1..=util::size::MAX_GRID).contains(&h) => Ok((w, h)),
+        Some(_) => Err(format!("sizes go from 1 to {}", util::size::MAX_GRID)),
+        None => Err(format!("{}: expected WxH or a preset", text)),
+    }
+}
+
+/// Side or corner kept in place by a resize, `-1` being west or north.
+fn parse_anchor(text: &str) -> Result<(isize, isize), String> {
+    match text.to_ascii_lowercase().as_str() {
+        "nw" => Ok((-1, -1)),
+        "n" => Ok((0, -1)),
+        "ne" => Ok((1, -1)),
+        "w" => Ok((-1, 0)),
+        "c" => Ok((0, 0)),
+        "e" => Ok((1, 0)),
+        "sw" => Ok((-1, 1)),
+        "s" => Ok((0, 1)),
+        "se" => Ok((1, 1)),
+        a => Err(format!("{}: anchors are nw n ne w c e sw s se", a)),
+    }
+}
+
+/// Ctrl + N starts a new drawing, Enter with the resize tool types its size.
+fn on_keys(
+    keys: Res<Input<KeyCode>>,
+    tool: Res<SelectedTool>,
+    grid: Option<Res<Grid>>,
+    prompt: Option<Res<Prompt>>,
+    cursor: Option<Res<TextCursorState>>,
+    mut open_prompt: EventWriter<OpenPrompt>,
+    mut message: EventWriter<ShowMessage>,
+) {
+    let Some(grid) = grid else { return };
+    if prompt.is_some() || cursor.is_some() { return; }
+    let presets = util::misc::PRESETS.iter().map(|(name, _, _)| *name).collect::<Vec<&str>>();
+
+    if keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) && keys.just_pressed(KeyCode::N) {
+        message.send(ShowMessage(format!("WxH or {}", presets.join(" "))));
+        open_prompt.send(OpenPrompt {
+            kind: PromptKind::NewDrawing,
+            label: "New:".to_string(),
+            default: presets[0].to_string(),
+        });
+    } else if tool.0 == Tools::Resize && keys.just_pressed(KeyCode::Return) {
+        message.send(ShowMessage("Anchors: nw n ne w c e sw s se".to_string()));
+        open_prompt.send(OpenPrompt {
+            kind: PromptKind::Resize,
+            label: "Size:".to_string(),
+            default: format!("{}x{} nw", grid.w, grid.h),
+        });
+    }
+}
+
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut new_project: EventWriter<NewProject>,
+    mut resize_grid: EventWriter<GridResized>,
+    mut message: EventWriter<ShowMessage>,
+    grid: Option<ResMut<Grid>>,
+) {
+    let Some(mut grid) = grid else { return };
+    for PromptSubmitted(kind, text) in submitted.iter() {
+        if text.is_empty() { continue }
+        match kind {
+            PromptKind::NewDrawing => match parse_size(text) {
+                Ok((w, h)) => new_project.send(NewProject(w, h)),
+                Err(e) => message.send(ShowMessage(e)),
+            },
+            PromptKind::Resize => {
+                let (size, anchor) = text.rsplit_once(' ').unwrap_or((text, "nw"));
+                let ((w, h), (ax, ay)) = match parse_size(size).and_then(|size| Ok((size, parse_anchor(anchor)?))) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        message.send(ShowMessage(e));
+                        continue;
+                    }
+                };
+
+                // Sides away from the anchor move
+                let (dw, dh) = (grid.w as isize - w as isize, grid.h as isize - h as isize);
+                grid.x0 += (ax + 1) * dw / 2;
+                grid.y0 += (ay + 1) * dh / 2;
+                grid.w = w;
+                grid.h = h;
+                resize_grid.send(GridResized);
+            }
+            _ => (),
+        }
+    }
+}
+
 fn cleanup() {
 
 }
\ No newline at end of file
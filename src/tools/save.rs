@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::{AppState, mouse, util};
+use crate::loading::Textures;
+use crate::mouse::{ButtonId, Clicked};
+use crate::project::{self, Document, SaveProject};
+use crate::tools::Tools;
+use crate::ui::{OpenPrompt, PromptKind, PromptSubmitted};
+
+pub(crate) struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems((update, on_prompt).in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 13,
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Save,
+            shortcut: 'w',
+            priority: util::tool_priority::SAVE,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Save),
+            hover_click: false,
+        });
+}
+
+/// Left click saves to the current document, right click is "Save As".
+fn update(
+    mut clicked: EventReader<Clicked>,
+    mut save: EventWriter<SaveProject>,
+    mut prompt: EventWriter<OpenPrompt>,
+    document: Res<Document>,
+) {
+    for Clicked(id, right) in clicked.iter() {
+        let ButtonId::Tool(Tools::Save) = id else { continue };
+
+        match (&document.path, right) {
+            (Some(path), false) => save.send(SaveProject(path.clone())),
+            (path, _) => prompt.send(OpenPrompt {
+                kind: PromptKind::SaveAs,
+                label: "Save as:".to_string(),
+                default: path.as_ref()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or(util::misc::DEFAULT_PROJECT.to_string()),
+            }),
+        }
+    }
+}
+
+fn on_prompt(
+    mut submitted: EventReader<PromptSubmitted>,
+    mut save: EventWriter<SaveProject>,
+) {
+    for PromptSubmitted(kind, path) in submitted.iter() {
+        if *kind != PromptKind::SaveAs || path.is_empty() { continue }
+        save.send(SaveProject(project::with_extension(path)));
+    }
+}
+
+fn cleanup() {
+
+}
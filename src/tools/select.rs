@@ -0,0 +1,511 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy_text_mode::{TextModeSpriteSheetBundle, TextModeTextureAtlasSprite};
+
+use crate::{AppState, mouse, util};
+use crate::grid::{Grid, grid_x, grid_y, GridChanged, HoveredTile, Layout, Pan, Tile, Zoom};
+use crate::loading::Textures;
+use crate::mouse::ButtonId;
+use crate::palette::{Colored, TilePalette};
+use crate::quick_tiles::Selection;
+use crate::screen::Screen;
+use crate::tilesets::{Tileset, Tilesets, TilesetSprite};
+use crate::toolbar::{SelectedTool, UpdateToolbar};
+use crate::tools::{export, import, Tools};
+use crate::ui::{Prompt, ShowMessage};
+
+pub(crate) struct SelectPlugin;
+
+impl Plugin for SelectPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(SelectState::default())
+            .insert_resource(SelectClipboard(None))
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems(
+                (on_tool_change, update, on_keys, update_floating, update_outline)
+                    .chain()
+                    .in_set(OnUpdate(AppState::Editor))
+            )
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+#[derive(Component)]
+struct SelectUI;
+
+#[derive(Component)]
+struct Outline(usize);
+
+#[derive(Component)]
+struct FloatingTile;
+
+/// Rectangle of grid positions.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Area {
+    x: isize,
+    y: isize,
+    w: usize,
+    h: usize,
+}
+
+impl Area {
+    fn from_corners(a: (isize, isize), b: (isize, isize)) -> Self {
+        Area {
+            x: a.0.min(b.0),
+            y: a.1.min(b.1),
+            w: (a.0 - b.0).unsigned_abs() + 1,
+            h: (a.1 - b.1).unsigned_abs() + 1,
+        }
+    }
+
+    fn contains(&self, (x, y): (isize, isize)) -> bool {
+        x >= self.x && x < self.x + self.w as isize && y >= self.y && y < self.y + self.h as isize
+    }
+
+    /// Positions, row by row.
+    fn positions(&self) -> Vec<(isize, isize)> {
+        let mut positions = Vec::with_capacity(self.w * self.h);
+        for y in self.y..(self.y + self.h as isize) {
+            for x in self.x..(self.x + self.w as isize) {
+                positions.push((x, y));
+            }
+        }
+        positions
+    }
+
+    /// Moved inside the grid so committing doesn't drop tiles, against its top left corner when bigger.
+    fn clamped(self, grid: &Grid) -> Self {
+        let x = self.x.min(grid.x0 + grid.w as isize - self.w as isize).max(grid.x0);
+        let y = self.y.min(grid.y0 + grid.h as isize - self.h as isize).max(grid.y0);
+        Area { x, y, ..self }
+    }
+}
+
+/// Tiles lifted from the grid or pasted, drawn above it until committed.
+struct Floating {
+    tiles: Vec<Tile>,
+    entities: Vec<Entity>,
+    origin: Option<(isize, isize)>,
+}
+
+#[derive(Default)]
+enum Drag {
+    #[default]
+    None,
+    Marquee((isize, isize)),
+    Move((isize, isize)),
+}
+
+#[derive(Resource, Default)]
+struct SelectState {
+    area: Option<Area>,
+    floating: Option<Floating>,
+    drag: Drag,
+}
+
+#[derive(Resource)]
+struct SelectClipboard(Option<(usize, usize, Vec<Tile>)>);
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 4,
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Select,
+            shortcut: 's',
+            priority: util::tool_priority::SELECT,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Select),
+            hover_click: false,
+        });
+}
+
+fn erased(grid: &Grid, selection: &Selection) -> Tile {
+    if grid.active > 0 { return Tile::empty(); }
+    Tile {
+        bg: selection.bg,
+        fg: selection.fg,
+        index: 0,
+        ..default()
+    }
+}
+
+fn spawn_floating(
+    commands: &mut Commands,
+    tilesets: &Res<Tilesets>,
+    tiles: Vec<Tile>,
+    origin: Option<(isize, isize)>,
+) -> Floating {
+    let entities = tiles.iter().map(|tile| {
+        commands
+            .spawn(TextModeSpriteSheetBundle {
+                sprite: TextModeTextureAtlasSprite {
+                    alpha: 1.,
+                    index: tile.index,
+                    anchor: Anchor::BottomLeft,
+                    rotation: tile.rotation,
+                    flip_x: tile.flip.0,
+                    flip_y: tile.flip.1,
+                    ..Default::default()
+                },
+                texture_atlas: tilesets.atlas(),
+                ..Default::default()
+            })
+            .insert(Colored { bg: tile.bg, fg: tile.fg })
+            .insert(TilesetSprite)
+            .insert(FloatingTile)
+            .insert(SelectUI)
+            .id()
+    }).collect();
+
+    Floating { tiles, entities, origin }
+}
+
+/// Removes the area content from the grid.
+fn lift(
+    commands: &mut Commands,
+    tilesets: &Res<Tilesets>,
+    grid: &mut Grid,
+    grid_changed: &mut EventWriter<GridChanged>,
+    selection: &Selection,
+    area: &Area,
+) -> Floating {
+    let positions = area.positions();
+    let blank = erased(grid, selection);
+    let tiles = positions.iter().map(|pos| {
+        let Some(tile) = grid.tiles_mut().get_mut(pos) else { return blank.clone() };
+        std::mem::replace(tile, blank.clone())
+    }).collect();
+    grid_changed.send(GridChanged(positions));
+    spawn_floating(commands, tilesets, tiles, Some((area.x, area.y)))
+}
+
+/// Writes floating tiles to the grid at the area position.
+fn commit(
+    commands: &mut Commands,
+    grid: &mut Grid,
+    grid_changed: &mut EventWriter<GridChanged>,
+    state: &mut SelectState,
+) {
+    let (Some(floating), Some(area)) = (state.floating.take(), state.area) else { return };
+
+    let positions = area.positions();
+    for (pos, tile) in positions.iter().zip(floating.tiles.into_iter()) {
+        let Some(t) = grid.tiles_mut().get_mut(pos) else { continue };
+        *t = tile;
+    }
+    grid_changed.send(GridChanged(positions));
+    floating.entities.iter().for_each(|e| commands.entity(*e).despawn_recursive());
+}
+
+fn discard(commands: &mut Commands, state: &mut SelectState) {
+    if let Some(floating) = state.floating.take() {
+        floating.entities.iter().for_each(|e| commands.entity(*e).despawn_recursive());
+    }
+}
+
+fn on_tool_change(
+    mut commands: Commands,
+    mut ev: EventReader<UpdateToolbar>,
+    mut state: ResMut<SelectState>,
+    mut grid: ResMut<Grid>,
+    mut grid_changed: EventWriter<GridChanged>,
+    tool: Res<SelectedTool>,
+) {
+    if ev.is_empty() { return; }
+    ev.clear();
+    if tool.0 == Tools::Select { return; }
+
+    commit(&mut commands, &mut grid, &mut grid_changed, &mut state);
+    state.area = None;
+    state.drag = Drag::None;
+}
+
+fn update(
+    mut commands: Commands,
+    mut state: ResMut<SelectState>,
+    mut grid: ResMut<Grid>,
+    mut grid_changed: EventWriter<GridChanged>,
+    tool: Res<SelectedTool>,
+    mouse: Res<Input<MouseButton>>,
+    hovered: Res<HoveredTile>,
+    selection: Res<Selection>,
+    tilesets: Res<Tilesets>,
+) {
+    if tool.0 != Tools::Select { return; }
+
+    if !mouse.pressed(MouseButton::Left) {
+        state.drag = Drag::None;
+        return;
+    }
+
+    let Some(pos) = hovered.0 else { return };
+
+    if mouse.just_pressed(MouseButton::Left) {
+        if state.area.map_or(false, |area| area.contains(pos)) {
+            // Drag the selection
+            if state.floating.is_none() {
+                let area = state.area.unwrap();
+                state.floating = Some(lift(&mut commands, &tilesets, &mut grid, &mut grid_changed, &selection, &area));
+            }
+            state.drag = Drag::Move(pos);
+        } else {
+            // New selection
+            commit(&mut commands, &mut grid, &mut grid_changed, &mut state);
+            state.area = Some(Area::from_corners(pos, pos));
+            state.drag = Drag::Marquee(pos);
+        }
+        return;
+    }
+
+    match state.drag {
+        Drag::Marquee(anchor) => {
+            state.area = Some(Area::from_corners(anchor, pos));
+        }
+        Drag::Move(last) => {
+            if let Some(area) = state.area {
+                let moved = Area { x: area.x + pos.0 - last.0, y: area.y + pos.1 - last.1, ..area };
+                state.area = Some(moved.clamped(&grid));
+            }
+            state.drag = Drag::Move(pos);
+        }
+        Drag::None => (),
+    }
+}
+
+fn copy(
+    state: &SelectState,
+    grid: &Grid,
+    clipboard: &mut SelectClipboard,
+    palette: &TilePalette,
+) -> bool {
+    let Some(area) = state.area else { return false };
+
+    let tiles = match state.floating {
+        Some(ref floating) => floating.tiles.clone(),
+        None => area.positions().iter()
+            .map(|pos| grid.tiles().get(pos).cloned().unwrap_or_default())
+            .collect(),
+    };
+
+    let export = export::to_tuples(tiles.iter().enumerate().map(|(i, tile)| {
+        (i % area.w, area.h - 1 - i / area.w, tile)
+    }), palette);
+    if let Err(e) = cli_clipboard::set_contents(export) {
+        warn!("Couldn't copy to clipboard: {}", e);
+    }
+
+    clipboard.0 = Some((area.w, area.h, tiles));
+    true
+}
+
+/// Reads tiles copied in the export format, falling back on the internal clipboard.
+fn paste_content(clipboard: &SelectClipboard, tileset: &Tileset, palette: &TilePalette) -> Option<(usize, usize, Vec<Tile>)> {
+    let from_clipboard = cli_clipboard::get_contents().ok().and_then(|text| {
+        let tuples = import::parse_tuples(&text, tileset.count(), palette.tuple_count()).ok()?;
+        let w = tuples.iter().map(|t| t.0).max()? + 1;
+        let h = tuples.iter().map(|t| t.1).max()? + 1;
+
+        let mut tiles = vec![Tile::default(); w * h];
+        for (x, y, index, bg, fg, flip_x, rotation, flip_y) in tuples {
+            let (bg, fg) = (palette.from_tuple(bg), palette.from_tuple(fg));
+            tiles[(h - 1 - y) * w + x] = Tile { bg, fg, index, flip: (flip_x, flip_y), rotation };
+        }
+        Some((w, h, tiles))
+    });
+
+    from_clipboard.or(clipboard.0.clone())
+}
+
+fn on_keys(
+    mut commands: Commands,
+    mut state: ResMut<SelectState>,
+    mut clipboard: ResMut<SelectClipboard>,
+    mut grid: ResMut<Grid>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut message: EventWriter<ShowMessage>,
+    tool: Res<SelectedTool>,
+    keys: Res<Input<KeyCode>>,
+    hovered: Res<HoveredTile>,
+    selection: Res<Selection>,
+    tilesets: Res<Tilesets>,
+    palette: Res<TilePalette>,
+    prompt: Option<Res<Prompt>>,
+) {
+    if tool.0 != Tools::Select || prompt.is_some() { return; }
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if ctrl && keys.just_pressed(KeyCode::C) {
+        if copy(&state, &grid, &mut clipboard, &palette) { message.send(ShowMessage("Copied".to_string())); }
+    } else if ctrl && keys.just_pressed(KeyCode::X) {
+        if !copy(&state, &grid, &mut clipboard, &palette) { return; }
+        if state.floating.is_some() {
+            discard(&mut commands, &mut state);
+        } else if let Some(area) = state.area {
+            erase(&mut grid, &mut grid_changed, &selection, &area);
+        }
+    } else if ctrl && keys.just_pressed(KeyCode::V) {
+        let Some((w, h, tiles)) = paste_content(&clipboard, tilesets.active(), &palette) else { return };
+        commit(&mut commands, &mut grid, &mut grid_changed, &mut state);
+
+        let (x, y) = state.area.map(|a| (a.x, a.y))
+            .or(hovered.0)
+            .unwrap_or((grid.x0, grid.y0));
+        state.area = Some(Area { x, y, w, h }.clamped(&grid));
+        state.floating = Some(spawn_floating(&mut commands, &tilesets, tiles, None));
+    } else if keys.any_just_pressed([KeyCode::Delete, KeyCode::Back]) {
+        if state.floating.is_some() {
+            discard(&mut commands, &mut state);
+        } else if let Some(area) = state.area {
+            erase(&mut grid, &mut grid_changed, &selection, &area);
+        }
+    } else if keys.just_pressed(KeyCode::Return) {
+        commit(&mut commands, &mut grid, &mut grid_changed, &mut state);
+    } else if keys.just_pressed(KeyCode::Escape) {
+        // Lifted tiles go back to their place, pasted ones are dropped
+        let origin = state.floating.as_ref().and_then(|f| f.origin);
+        if let (Some((x, y)), Some(area)) = (origin, state.area.as_mut()) {
+            area.x = x;
+            area.y = y;
+            commit(&mut commands, &mut grid, &mut grid_changed, &mut state);
+        } else {
+            discard(&mut commands, &mut state);
+        }
+        state.area = None;
+    } else {
+        // Nudge
+        let (mut dx, mut dy) = (0, 0);
+        if keys.just_pressed(KeyCode::Left) { dx = -1; }
+        if keys.just_pressed(KeyCode::Right) { dx = 1; }
+        if keys.just_pressed(KeyCode::Up) { dy = -1; }
+        if keys.just_pressed(KeyCode::Down) { dy = 1; }
+        if dx == 0 && dy == 0 { return; }
+        let Some(area) = state.area else { return };
+
+        if state.floating.is_none() {
+            state.floating = Some(lift(&mut commands, &tilesets, &mut grid, &mut grid_changed, &selection, &area));
+        }
+        state.area = Some(Area { x: area.x + dx, y: area.y + dy, ..area }.clamped(&grid));
+    }
+}
+
+fn erase(
+    grid: &mut Grid,
+    grid_changed: &mut EventWriter<GridChanged>,
+    selection: &Selection,
+    area: &Area,
+) {
+    let positions = area.positions();
+    let blank = erased(grid, selection);
+    for pos in positions.iter() {
+        let Some(tile) = grid.tiles_mut().get_mut(pos) else { continue };
+        *tile = blank.clone();
+    }
+    grid_changed.send(GridChanged(positions));
+}
+
+fn update_floating(
+    state: Res<SelectState>,
+    grid: Res<Grid>,
+    zoom: Res<Zoom>,
+    pan: Res<Pan>,
+    screen: Res<Screen>,
+    tilesets: Res<Tilesets>,
+    mut transforms: Query<&mut Transform, With<FloatingTile>>,
+) {
+    let (Some(floating), Some(area)) = (&state.floating, state.area) else { return };
+    let layout = Layout::new(&tilesets, &zoom, &pan, &screen);
+
+    for ((x, y), e) in area.positions().iter().zip(floating.entities.iter()) {
+        let Ok(mut transform) = transforms.get_mut(*e) else { continue };
+        transform.translation = Vec3::new(
+            grid_x(*x, grid.x0, grid.w, layout),
+            grid_y(*y, grid.y0, grid.h, layout),
+            util::z::GRID_FLOATING,
+        );
+        transform.scale = Vec3::new(zoom.0, zoom.0, 1.);
+    }
+}
+
+/// Marching ants around the selected area.
+fn update_outline(
+    mut commands: Commands,
+    state: Res<SelectState>,
+    grid: Res<Grid>,
+    zoom: Res<Zoom>,
+    pan: Res<Pan>,
+    screen: Res<Screen>,
+    tilesets: Res<Tilesets>,
+    mut drawn: Local<Option<(Area, isize, isize, usize, usize, Layout)>>,
+    mut frame: Local<usize>,
+    mut outline: Query<(Entity, &Outline, &mut Sprite)>,
+) {
+    let layout = Layout::new(&tilesets, &zoom, &pan, &screen);
+    let key = state.area.map(|area| (area, grid.x0, grid.y0, grid.w, grid.h, layout));
+    if *drawn != key {
+        *drawn = key;
+        outline.iter().for_each(|(e, _, _)| commands.entity(e).despawn_recursive());
+
+        let Some(area) = state.area else { return };
+        let cell = layout.cell;
+        let dash = cell / 2.;
+        let left = grid_x(area.x, grid.x0, grid.w, layout);
+        let right = left + cell.x * area.w as f32;
+        let bottom = grid_y(area.y + area.h as isize - 1, grid.y0, grid.h, layout);
+        let top = bottom + cell.y * area.h as f32;
+
+        // Dashes clockwise from the top left corner
+        let mut dashes = Vec::new();
+        for i in 0..(area.w * 2) { dashes.push((left + dash.x * i as f32, top - 1., dash.x, 1.)); }
+        for i in 0..(area.h * 2) { dashes.push((right - 1., top - dash.y * (i + 1) as f32, 1., dash.y)); }
+        for i in 0..(area.w * 2) { dashes.push((right - dash.x * (i + 1) as f32, bottom, dash.x, 1.)); }
+        for i in 0..(area.h * 2) { dashes.push((left, bottom + dash.y * i as f32, 1., dash.y)); }
+
+        for (n, (x, y, w, h)) in dashes.into_iter().enumerate() {
+            commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::new(w, h)),
+                        anchor: Anchor::BottomLeft,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(x, y, util::z::GRID_OUTLINE),
+                    ..Default::default()
+                })
+                .insert(Outline(n))
+                .insert(SelectUI);
+        }
+    }
+
+    *frame = (*frame + 1) % 32;
+    let phase = 4 - *frame / 8;
+    for (_, Outline(n), mut sprite) in outline.iter_mut() {
+        sprite.color = if (n + phase) % 4 < 2 { Color::WHITE } else { Color::BLACK };
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    mut state: ResMut<SelectState>,
+    query: Query<Entity, With<SelectUI>>,
+) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    *state = SelectState::default();
+}
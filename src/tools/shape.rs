@@ -0,0 +1,343 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy_text_mode::{TextModeSpriteSheetBundle, TextModeTextureAtlasSprite};
+
+use crate::{AppState, mouse, util};
+use crate::grid::{Grid, grid_x, grid_y, GridChanged, HoveredTile, Layout, Pan, Tile, Zoom};
+use crate::loading::Textures;
+use crate::mouse::ButtonId;
+use crate::palette::Colored;
+use crate::quick_tiles::Selection;
+use crate::screen::Screen;
+use crate::tilesets::{Charmap, Tilesets, TilesetSprite};
+use crate::toolbar::SelectedTool;
+use crate::tools::frame::{self, Part, Style};
+use crate::tools::Tools;
+use crate::ui::{Prompt, ShowMessage};
+
+pub(crate) struct ShapePlugin;
+
+impl Plugin for ShapePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(ShapeSettings { shape: Shape::Line, boxes: false })
+            .insert_resource(Preview::default())
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems((settings, update, update_preview).chain().in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+#[derive(Component)]
+struct ShapeUI;
+
+#[derive(Component)]
+pub(super) struct PreviewTile;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Shape {
+    Line,
+    Rectangle,
+    FilledRectangle,
+    Ellipse,
+}
+
+impl Shape {
+    fn icon(&self) -> usize {
+        match self {
+            Shape::Line => 18,
+            Shape::Rectangle => 19,
+            Shape::FilledRectangle => 20,
+            Shape::Ellipse => 21,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ShapeSettings {
+    shape: Shape,
+    /// Draw edges with the box-drawing glyphs of the tileset instead of the selected tile,
+    /// resolved like single frames, diagonals and points with `╱`, `╲` and `┼` or their ASCII look-alikes.
+    boxes: bool,
+}
+
+impl ShapeSettings {
+    fn describe(&self) -> String {
+        format!("Shape: {:?}, {}", self.shape, if self.boxes { "box glyphs" } else { "selected tile" })
+    }
+}
+
+/// Tiles shown while dragging a shape or a frame, drawn when the button is released.
+#[derive(Resource, Default)]
+pub(super) struct Preview(pub Vec<((isize, isize), Tile)>);
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+    settings: Res<ShapeSettings>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: settings.shape.icon(),
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Shape,
+            shortcut: 'u',
+            priority: util::tool_priority::SHAPE,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Shape),
+            hover_click: false,
+        });
+}
+
+/// 1 to 4 pick a line, rectangle, filled rectangle or ellipse, 5 toggles box glyphs.
+fn settings(
+    tool: Res<SelectedTool>,
+    keys: Res<Input<KeyCode>>,
+    prompt: Option<Res<Prompt>>,
+    mut settings: ResMut<ShapeSettings>,
+    mut message: EventWriter<ShowMessage>,
+    mut icon: Query<(&crate::toolbar::Tool, &mut TextureAtlasSprite)>,
+) {
+    if tool.0 != Tools::Shape || prompt.is_some() { return; }
+    if keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) { return; }
+
+    let mut changed = true;
+    if keys.just_pressed(KeyCode::Key1) { settings.shape = Shape::Line; }
+    else if keys.just_pressed(KeyCode::Key2) { settings.shape = Shape::Rectangle; }
+    else if keys.just_pressed(KeyCode::Key3) { settings.shape = Shape::FilledRectangle; }
+    else if keys.just_pressed(KeyCode::Key4) { settings.shape = Shape::Ellipse; }
+    else if keys.just_pressed(KeyCode::Key5) { settings.boxes = !settings.boxes; }
+    else { changed = false; }
+
+    if changed {
+        for (tool, mut sprite) in icon.iter_mut() {
+            if tool.id == Tools::Shape { sprite.index = settings.shape.icon(); }
+        }
+        message.send(ShowMessage(settings.describe()));
+    }
+}
+
+/// Keeps lines horizontal, vertical or diagonal, and other shapes as wide as high.
+fn constrain(shape: Shape, a: (isize, isize), b: (isize, isize)) -> (isize, isize) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    if shape == Shape::Line && dx.abs() > 2 * dy.abs() { return (b.0, a.1); }
+    if shape == Shape::Line && dy.abs() > 2 * dx.abs() { return (a.0, b.1); }
+    square(a, b)
+}
+
+pub(super) fn square(a: (isize, isize), b: (isize, isize)) -> (isize, isize) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let d = dx.abs().max(dy.abs());
+    (a.0 + if dx < 0 { -d } else { d }, a.1 + if dy < 0 { -d } else { d })
+}
+
+/// Bresenham's line.
+fn line(a: (isize, isize), b: (isize, isize)) -> Vec<(isize, isize)> {
+    let (dx, dy) = ((b.0 - a.0).abs(), -(b.1 - a.1).abs());
+    let (sx, sy) = ((b.0 - a.0).signum(), (b.1 - a.1).signum());
+    let (mut x, mut y, mut err) = (a.0, a.1, dx + dy);
+    let mut points = vec![];
+    loop {
+        points.push((x, y));
+        if (x, y) == b { break; }
+        let e2 = 2 * err;
+        if e2 >= dy { err += dy; x += sx; }
+        if e2 <= dx { err += dx; y += sy; }
+    }
+    points
+}
+
+/// Glyph of a position on the edges of a shape.
+#[derive(Copy, Clone)]
+enum Glyph {
+    /// Part of a single frame, in the order of [crate::tilesets::Frame::tiles].
+    Part(usize),
+    /// Box-drawing character and its ASCII look-alike.
+    Char(char, char),
+}
+
+const POINT: Glyph = Glyph::Char('┼', '+');
+
+/// Positions of a shape, with the glyph of those on its edges.
+fn plot(shape: Shape, a: (isize, isize), b: (isize, isize)) -> Vec<((isize, isize), Option<Glyph>)> {
+    let (x0, x1, y0, y1) = (a.0.min(b.0), a.0.max(b.0), a.1.min(b.1), a.1.max(b.1));
+    let (w, h) = (x1 - x0, y1 - y0);
+    let area = (y0..=y1).flat_map(|y| (x0..=x1).map(move |x| (x, y)));
+    let edge = move |pos: (isize, isize)| {
+        if w == 0 && h == 0 { return Some(POINT); }
+        frame::part(a, b, pos).map(Glyph::Part)
+    };
+
+    match shape {
+        Shape::Line => {
+            // Rows go down the grid
+            let glyph = if w == 0 && h == 0 { POINT }
+                else if 2 * h < w { Glyph::Part(1) }
+                else if 2 * w < h { Glyph::Part(7) }
+                else if (b.0 - a.0) * (b.1 - a.1) > 0 { Glyph::Char('╲', '\\') }
+                else { Glyph::Char('╱', '/') };
+            line(a, b).into_iter().map(|pos| (pos, Some(glyph))).collect()
+        }
+        Shape::Rectangle => area.filter_map(|pos| edge(pos).map(|c| (pos, Some(c)))).collect(),
+        Shape::FilledRectangle => area.map(|pos| (pos, edge(pos))).collect(),
+        Shape::Ellipse => {
+            let (cx, cy) = ((x0 + x1) as f32 / 2., (y0 + y1) as f32 / 2.);
+            let (rx, ry) = (w as f32 / 2. + 0.5, h as f32 / 2. + 0.5);
+            let inside = |x: isize, y: isize| ((x as f32 - cx) / rx).powi(2) + ((y as f32 - cy) / ry).powi(2) <= 1.;
+            area.filter(|&(x, y)| inside(x, y) && !(inside(x - 1, y) && inside(x + 1, y) && inside(x, y - 1) && inside(x, y + 1)))
+                .map(|pos| (pos, None))
+                .collect()
+        }
+    }
+}
+
+fn tile(glyph: Option<Glyph>, settings: &ShapeSettings, selection: &Selection, charmap: &Charmap, parts: Option<&[Part; 8]>) -> Tile {
+    let tile = Tile {
+        bg: selection.bg,
+        fg: selection.fg,
+        index: selection.index,
+        flip: selection.flip,
+        rotation: selection.rotation,
+    };
+    let part = match glyph.filter(|_| settings.boxes) {
+        Some(Glyph::Part(i)) => parts.map(|parts| parts[i]),
+        Some(Glyph::Char(c, ascii)) => charmap.tile(c).or(charmap.tile(ascii)).map(|index| (index, (false, false), 0)),
+        None => None,
+    };
+    match part {
+        Some((index, flip, rotation)) => Tile { index, flip, rotation, ..tile },
+        None => tile,
+    }
+}
+
+/// Start and end of a drag started on the grid, ends on the last hovered tile.
+pub(super) fn drag(
+    drag: &mut Option<((isize, isize), (isize, isize))>,
+    mouse: &Input<MouseButton>,
+    hovered: &HoveredTile,
+) -> Option<((isize, isize), (isize, isize))> {
+    if mouse.just_pressed(MouseButton::Left) { *drag = hovered.0.map(|pos| (pos, pos)); }
+    let (start, end) = drag.as_mut()?;
+    if let Some(pos) = hovered.0 { *end = pos; }
+    Some((*start, *end))
+}
+
+/// Writes the previewed tiles in the grid.
+pub(super) fn draw(
+    preview: &mut Preview,
+    grid: &mut Grid,
+    grid_changed: &mut EventWriter<GridChanged>,
+) {
+    let mut positions = Vec::with_capacity(preview.0.len());
+    for (pos, tile) in preview.0.drain(..) {
+        let Some(t) = grid.tiles_mut().get_mut(&pos) else { continue };
+        *t = tile;
+        positions.push(pos);
+    }
+    grid_changed.send(GridChanged(positions));
+}
+
+/// Dragging previews the shape, releasing the button draws it.
+fn update(
+    mut dragged: Local<Option<((isize, isize), (isize, isize))>>,
+    mut preview: ResMut<Preview>,
+    grid: Option<ResMut<Grid>>,
+    mut grid_changed: EventWriter<GridChanged>,
+    tool: Res<SelectedTool>,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    hovered: Res<HoveredTile>,
+    selection: Res<Selection>,
+    settings: Res<ShapeSettings>,
+    tilesets: Res<Tilesets>,
+) {
+    if tool.0 != Tools::Shape {
+        if dragged.take().is_some() { preview.0.clear(); }
+        return;
+    }
+
+    let Some(mut grid) = grid else { return };
+    let Some((start, mut end)) = drag(&mut dragged, &mouse, &hovered) else { return };
+    if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) { end = constrain(settings.shape, start, end); }
+    let tileset = tilesets.active();
+    let parts = frame::resolve(Style::Single, tileset);
+    preview.0 = plot(settings.shape, start, end).into_iter()
+        .filter(|(pos, _)| grid.contains(*pos))
+        .map(|(pos, glyph)| (pos, tile(glyph, &settings, &selection, &tileset.charmap, parts.as_ref())))
+        .collect();
+
+    if !mouse.pressed(MouseButton::Left) {
+        draw(&mut preview, &mut grid, &mut grid_changed);
+        *dragged = None;
+    }
+}
+
+pub(super) fn update_preview(
+    mut commands: Commands,
+    preview: Res<Preview>,
+    grid: Res<Grid>,
+    zoom: Res<Zoom>,
+    pan: Res<Pan>,
+    screen: Res<Screen>,
+    tilesets: Res<Tilesets>,
+    mut drawn: Local<Option<(Vec<((isize, isize), Tile)>, Layout)>>,
+    sprites: Query<Entity, With<PreviewTile>>,
+) {
+    let layout = Layout::new(&tilesets, &zoom, &pan, &screen);
+    let key = Some((preview.0.clone(), layout)).filter(|(tiles, _)| !tiles.is_empty());
+    if *drawn == key { return; }
+    *drawn = key;
+
+    sprites.iter().for_each(|e| commands.entity(e).despawn_recursive());
+    for ((x, y), tile) in preview.0.iter() {
+        commands
+            .spawn(TextModeSpriteSheetBundle {
+                sprite: TextModeTextureAtlasSprite {
+                    alpha: 1.,
+                    index: tile.index,
+                    anchor: Anchor::BottomLeft,
+                    rotation: tile.rotation,
+                    flip_x: tile.flip.0,
+                    flip_y: tile.flip.1,
+                    ..Default::default()
+                },
+                texture_atlas: tilesets.atlas(),
+                transform: Transform {
+                    translation: Vec3::new(
+                        grid_x(*x, grid.x0, grid.w, layout),
+                        grid_y(*y, grid.y0, grid.h, layout),
+                        util::z::GRID_FLOATING,
+                    ),
+                    scale: Vec3::new(zoom.0, zoom.0, 1.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Colored { bg: tile.bg, fg: tile.fg })
+            .insert(TilesetSprite)
+            .insert(PreviewTile)
+            .insert(ShapeUI);
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    mut preview: ResMut<Preview>,
+    query: Query<Entity, With<ShapeUI>>,
+) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    preview.0.clear();
+}
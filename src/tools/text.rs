@@ -1,13 +1,16 @@
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
+use bevy::window::ReceivedCharacter;
 
 use crate::{AppState, mouse, util};
-use crate::grid::{Grid, GridChanged, HoverTileIndexOverride};
+use crate::grid::{Grid, GridChanged, GridResized, HoverTileIndexOverride, Tile};
 use crate::loading::Textures;
 use crate::mouse::{ButtonId, Clicked};
 use crate::quick_tiles::Selection;
+use crate::tilesets::{Charmap, Tilesets};
 use crate::toolbar::{SelectedTool, UpdateToolbar};
 use crate::tools::Tools;
+use crate::ui::{Prompt, ShowMessage};
 
 pub(crate) struct TextPlugin;
 
@@ -15,7 +18,8 @@ impl Plugin for TextPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
-            .add_systems((update, on_click, on_type).in_set(OnUpdate(AppState::Editor)))
+            .insert_resource(TextSettings { wrap: false, insert: false, grow: false })
+            .add_systems((update, on_click, settings, on_type, on_paste).in_set(OnUpdate(AppState::Editor)))
             .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
 
     }
@@ -25,7 +29,7 @@ impl Plugin for TextPlugin {
 struct TextUI;
 
 #[derive(Resource)]
-struct TextCursorState {
+pub(crate) struct TextCursorState {
     start_x: isize,
     start_y: isize,
     current_x: isize,
@@ -33,6 +37,27 @@ struct TextCursorState {
     frame: usize,
 }
 
+#[derive(Resource)]
+struct TextSettings {
+    /// Words reaching the right edge of the grid continue on the next line.
+    wrap: bool,
+    /// Typing pushes the rest of the line right instead of replacing it.
+    insert: bool,
+    /// Pasted text that doesn't fit grows the grid instead of being clipped.
+    grow: bool,
+}
+
+impl TextSettings {
+    fn describe(&self) -> String {
+        format!(
+            "Text: {}, {}, {} pasted text",
+            if self.insert { "insert" } else { "overwrite" },
+            if self.wrap { "word wrap" } else { "no wrap" },
+            if self.grow { "grow to" } else { "clip" },
+        )
+    }
+}
+
 fn setup(
     mut commands: Commands,
     textures: Res<Textures>,
@@ -69,11 +94,12 @@ fn update(
     mut state: Option<ResMut<TextCursorState>>,
     mut cursor: Option<ResMut<HoverTileIndexOverride>>,
     selected: Res<SelectedTool>,
+    tilesets: Res<Tilesets>,
 ) {
     for UpdateToolbar in ev.iter() {
         if selected.0 == Tools::Text {
             commands.insert_resource(HoverTileIndexOverride {
-                index: 927,
+                index: tilesets.active().charmap.tile('_').unwrap_or(0),
                 visible: Visibility::Inherited,
                 force_x: None,
                 force_y: None,
@@ -118,49 +144,232 @@ fn on_click(
     }
 }
 
+/// Moves the text cursor, it is visible right away.
+fn move_cursor(state: &mut TextCursorState, hover_override: &mut Option<ResMut<HoverTileIndexOverride>>, x: isize, y: isize) {
+    state.current_x = x;
+    state.current_y = y;
+    state.frame = 0;
+    if let Some(ref mut hover_override) = hover_override {
+        hover_override.force_x = Some(x);
+        hover_override.force_y = Some(y);
+    }
+}
+
+/// Moves the tiles of a row right of `x` by one column, to the right or to the left.
+/// The cell freed at either end is blank.
+fn shift_row(grid: &mut Grid, (x, y): (isize, isize), right: bool, blank: &Tile, changed: &mut Vec<(isize, isize)>) {
+    let end = grid.x0 + grid.w as isize - 1;
+    let tiles = grid.tiles_mut();
+    if right {
+        for i in (x + 1..=end).rev() {
+            let Some(tile) = tiles.get(&(i - 1, y)).cloned() else { continue };
+            tiles.insert((i, y), tile);
+        }
+        tiles.insert((x, y), blank.clone());
+    } else {
+        for i in x..end {
+            let Some(tile) = tiles.get(&(i + 1, y)).cloned() else { continue };
+            tiles.insert((i, y), tile);
+        }
+        tiles.insert((end, y), blank.clone());
+    }
+    changed.extend((x..=end).map(|i| (i, y)));
+}
+
+/// Tile of a typed character, with the fallback glyph for the ones the tileset lacks but spaces.
+fn glyph(c: char, charmap: &Charmap, selection: &Selection) -> Option<Tile> {
+    let index = if c == ' ' { charmap.tile(' ') } else { charmap.glyph(c) }?;
+    Some(Tile { bg: selection.bg, fg: selection.fg, index, flip: (false, false), rotation: 0 })
+}
+
 fn on_type(
-    mut state: Option<ResMut<TextCursorState>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    state: Option<ResMut<TextCursorState>>,
     mut grid: ResMut<Grid>,
     mut grid_changed: EventWriter<GridChanged>,
     mut hover_override: Option<ResMut<HoverTileIndexOverride>>,
     selection: Res<Selection>,
+    settings: Res<TextSettings>,
     keys: Res<Input<KeyCode>>,
+    prompt: Option<Res<Prompt>>,
+    tilesets: Res<Tilesets>,
 ) {
-    let Some(mut state) = state else { return };
-    for key in keys.get_just_pressed() {
-        if let Some(char) = util::get_char(key) {
-            let (x, y) = (state.current_x, state.current_y);
-            let Some(index) = util::char_to_tile(char) else { continue };
-            let Some((ref mut tile, _)) = grid.tiles.get_mut(&(x as isize, y as isize)) else {continue};
-
-            // Update grid
-            tile.index = index;
-            tile.bg = selection.bg;
-            tile.fg = selection.fg;
-            grid_changed.send(GridChanged(vec![(x, y)]));
-
-            // Update state and cursor
-            state.current_x += 1;
-            if let Some(ref mut hover_override) = hover_override {
-                hover_override.force_x = Some(state.current_x);
+    let Some(mut state) = state else { chars.clear(); return };
+    if prompt.is_some() || keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) { chars.clear(); return; }
+    if grid.w == 0 || grid.h == 0 { return; }
+
+    let charmap = &tilesets.active().charmap;
+    let (left, right) = (grid.x0, grid.x0 + grid.w as isize);
+    let (top, bottom) = (grid.y0, grid.y0 + grid.h as isize);
+    let start = state.start_x.clamp(left, right - 1);
+    let blank = if grid.active > 0 { Tile::empty() } else {
+        Tile { bg: selection.bg, fg: selection.fg, ..default() }
+    };
+    let is_text = |tile: Option<&Tile>| tile.and_then(|t| charmap.char(t.index)).map_or(false, |c| !c.is_whitespace());
+
+    let (mut x, mut y) = (state.current_x, state.current_y);
+    let mut changed = vec![];
+
+    for ReceivedCharacter { char, .. } in chars.iter() {
+        if char.is_control() { continue }
+
+        // Past the edge, the word being typed moves to the next line, without wrapping typing stops there
+        if x >= right {
+            if !settings.wrap || y + 1 >= bottom { continue }
+            let mut word = right;
+            while word > start && is_text(grid.tiles().get(&(word - 1, y))) { word -= 1; }
+            if word == start || char.is_whitespace() { word = right; }
+            for i in word..right {
+                let Some(tile) = grid.tiles().get(&(i, y)).cloned() else { continue };
+                grid.tiles_mut().insert((start + i - word, y + 1), tile);
+                grid.tiles_mut().insert((i, y), blank.clone());
+                changed.extend([(i, y), (start + i - word, y + 1)]);
             }
-        } else if *key == KeyCode::Return {
-            state.current_x = state.start_x;
-            state.current_y += 1;
+            x = start + right - word;
+            y += 1;
+            if char.is_whitespace() { continue }
+        }
+        if !grid.contains((x, y)) { continue }
 
-            // Update cursor
-            if let Some(ref mut hover_override) = hover_override {
-                hover_override.force_x = Some(state.current_x);
-                hover_override.force_y = Some(state.current_y);
+        let tile = match glyph(*char, charmap, &selection) {
+            Some(tile) => Some(tile),
+            // Spaces the tileset has no glyph for skip over the drawing, unless inserting
+            None if *char == ' ' => Some(blank.clone()).filter(|_| settings.insert),
+            None => continue,
+        };
+        if let Some(tile) = tile {
+            if settings.insert { shift_row(&mut grid, (x, y), true, &blank, &mut changed); }
+            grid.tiles_mut().insert((x, y), tile);
+            changed.push((x, y));
+        }
+
+        x += 1;
+    }
+
+    let tab = util::misc::TAB_WIDTH as isize;
+    for key in keys.get_just_pressed() {
+        match key {
+            KeyCode::Return => { x = start; y = (y + 1).min(bottom - 1); }
+            KeyCode::Tab => { x = (start + ((x - start) / tab + 1) * tab).min(right - 1); }
+            KeyCode::Left => { x = (x - 1).max(left); }
+            KeyCode::Right => { x = (x + 1).min(right - 1); }
+            KeyCode::Up => { y = (y - 1).max(top); }
+            KeyCode::Down => { y = (y + 1).min(bottom - 1); }
+            KeyCode::Home => { x = start; }
+            KeyCode::End => {
+                x = (left..right).rev()
+                    .find(|i| is_text(grid.tiles().get(&(*i, y))))
+                    .map_or(start, |i| (i + 1).min(right - 1));
             }
-        } else if *key == KeyCode::Space {
-            // Update state and cursor
-            state.current_x += 1;
-            if let Some(ref mut hover_override) = hover_override {
-                hover_override.force_x = Some(state.current_x);
+            KeyCode::Back | KeyCode::Delete => {
+                if *key == KeyCode::Back {
+                    if x <= left { continue }
+                    x = x.min(right) - 1;
+                }
+                if !grid.contains((x, y)) { continue }
+                if settings.insert {
+                    shift_row(&mut grid, (x, y), false, &blank, &mut changed);
+                } else {
+                    grid.tiles_mut().insert((x, y), blank.clone());
+                    changed.push((x, y));
+                }
             }
+            _ => {}
+        }
+    }
+
+    if (x, y) != (state.current_x, state.current_y) { move_cursor(&mut state, &mut hover_override, x, y); }
+    if !changed.is_empty() { grid_changed.send(GridChanged(changed)); }
+}
+
+/// Ctrl + V writes the clipboard text from the cursor, following lines start under the first one.
+fn on_paste(
+    state: Option<ResMut<TextCursorState>>,
+    mut grid: ResMut<Grid>,
+    mut grid_changed: EventWriter<GridChanged>,
+    mut grid_resized: EventWriter<GridResized>,
+    mut hover_override: Option<ResMut<HoverTileIndexOverride>>,
+    mut message: EventWriter<ShowMessage>,
+    selection: Res<Selection>,
+    settings: Res<TextSettings>,
+    keys: Res<Input<KeyCode>>,
+    prompt: Option<Res<Prompt>>,
+    tilesets: Res<Tilesets>,
+) {
+    let Some(mut state) = state else { return };
+    if prompt.is_some() || !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) || !keys.just_pressed(KeyCode::V) { return; }
+    let text = match cli_clipboard::get_contents() {
+        Ok(text) if !text.is_empty() => text,
+        _ => {
+            message.send(ShowMessage("Clipboard is empty".to_string()));
+            return;
+        }
+    };
+
+    // Tabs go to the next stop, other control characters are dropped
+    let tab = util::misc::TAB_WIDTH as isize;
+    let start = state.start_x;
+    let (mut x, mut y) = (state.current_x, state.current_y);
+    let mut chars = vec![];
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 { (x, y) = (start, y + 1); }
+        for c in line.chars() {
+            if c == '\t' { x = start + ((x - start) / tab + 1) * tab; continue }
+            if c.is_control() { continue }
+            chars.push(((x, y), c));
+            x += 1;
+        }
+    }
+
+    if settings.grow {
+        let max = util::size::MAX_GRID;
+        let w = chars.iter().map(|((x, _), _)| x - grid.x0 + 1).max().unwrap_or(0).clamp(grid.w as isize, max as isize);
+        let h = (y - grid.y0 + 1).clamp(grid.h as isize, max as isize);
+        if (w as usize, h as usize) != (grid.w, grid.h) {
+            grid.w = w as usize;
+            grid.h = h as usize;
+            grid_resized.send(GridResized);
         }
     }
+
+    let charmap = &tilesets.active().charmap;
+    let mut changed = vec![];
+    for (pos, c) in chars {
+        if !grid.contains(pos) { continue }
+        let Some(tile) = glyph(c, charmap, &selection) else { continue };
+        grid.tiles_mut().insert(pos, tile);
+        changed.push(pos);
+    }
+    grid_changed.send(GridChanged(changed));
+
+    if grid.w > 0 && grid.h > 0 {
+        x = x.clamp(grid.x0, grid.x0 + grid.w as isize - 1);
+        y = y.clamp(grid.y0, grid.y0 + grid.h as isize - 1);
+    }
+    move_cursor(&mut state, &mut hover_override, x, y);
+}
+
+/// Insert toggles insert mode, Ctrl + W word wrap, Ctrl + G growing the grid on paste.
+fn settings(
+    tool: Res<SelectedTool>,
+    keys: Res<Input<KeyCode>>,
+    prompt: Option<Res<Prompt>>,
+    mut settings: ResMut<TextSettings>,
+    mut message: EventWriter<ShowMessage>,
+) {
+    if tool.0 != Tools::Text || prompt.is_some() { return; }
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+
+    if keys.just_pressed(KeyCode::Insert) {
+        settings.insert = !settings.insert;
+    } else if ctrl && keys.just_pressed(KeyCode::W) {
+        settings.wrap = !settings.wrap;
+    } else if ctrl && keys.just_pressed(KeyCode::G) {
+        settings.grow = !settings.grow;
+    } else {
+        return;
+    }
+    message.send(ShowMessage(settings.describe()));
 }
 
 fn cleanup(
@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::{AppState, mouse, util};
+use crate::loading::Textures;
+use crate::mouse::{ButtonId, Clicked};
+use crate::tilesets::{SwitchTileset, Tilesets};
+use crate::tools::Tools;
+
+pub(crate) struct TilesetPlugin;
+
+impl Plugin for TilesetPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(setup.in_schedule(OnEnter(AppState::Editor)))
+            .add_system(update.in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    textures: Res<Textures>,
+) {
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 17,
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            texture_atlas: textures.icons.clone(),
+            transform: Transform::from_xyz(0., 0., util::z::TOOLBAR_ICONS),
+            ..Default::default()
+        })
+        .insert(crate::toolbar::Tool {
+            id: Tools::Tileset,
+            shortcut: 'g',
+            priority: util::tool_priority::TILESET,
+        })
+        .insert(mouse::Clickable {
+            w: 16.0,
+            h: 16.0,
+            id: ButtonId::Tool(Tools::Tileset),
+            hover_click: false,
+        });
+}
+
+/// Left click switches to the next tileset, right click to the previous one.
+fn update(
+    mut clicked: EventReader<Clicked>,
+    mut switch: EventWriter<SwitchTileset>,
+    tilesets: Res<Tilesets>,
+) {
+    for Clicked(id, right) in clicked.iter() {
+        let ButtonId::Tool(Tools::Tileset) = id else { continue };
+        let count = tilesets.list.len();
+        let step = if *right { count - 1 } else { 1 };
+        switch.send(SwitchTileset((tilesets.active + step) % count));
+    }
+}
+
+fn cleanup() {
+
+}
@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::window::ReceivedCharacter;
+use bevy_text_mode::{TextModeSpriteSheetBundle, TextModeTextureAtlasSprite};
+
+use crate::{AppState, util};
+use crate::loading::Textures;
+use crate::screen::Screen;
+use crate::util::Palette;
+use crate::util::size::{CHAR, LEFT_MARGIN};
+
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ShowMessage>()
+            .add_event::<OpenPrompt>()
+            .add_event::<PromptSubmitted>()
+            .add_systems(
+                (show_message, open_prompt, update_prompt, anchor_status)
+                    .in_set(OnUpdate(AppState::Editor))
+            )
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+#[derive(Component)]
+struct TextUI;
+
+/// Displays a message on the status line for a few seconds.
+pub struct ShowMessage(pub String);
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PromptKind {
+    SaveAs,
+    Open,
+    Export,
+    Import,
+    RenameLayer,
+    LoadPalette,
+    EditColor,
+    NewDrawing,
+    Resize,
+}
+
+pub struct OpenPrompt {
+    pub kind: PromptKind,
+    pub label: String,
+    pub default: String,
+}
+
+pub struct PromptSubmitted(pub PromptKind, pub String);
+
+/// Line input, captures the keyboard while it exists.
+#[derive(Resource)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    label: String,
+    input: String,
+    entity: Entity,
+}
+
+/// Spawns [text] as a row of tiles, the returned entity is the parent of all characters.
+pub fn spawn_text(
+    commands: &mut Commands,
+    textures: &Res<Textures>,
+    text: &str,
+    position: Vec3,
+    fg: Color,
+    bg: Color,
+) -> Entity {
+    commands
+        .spawn(SpatialBundle::from_transform(Transform::from_translation(position)))
+        .with_children(|parent| {
+            for (i, c) in text.chars().enumerate() {
+                parent.spawn(TextModeSpriteSheetBundle {
+                    sprite: TextModeTextureAtlasSprite {
+                        bg,
+                        fg,
+                        alpha: 1.,
+                        index: util::char_to_tile(c).unwrap_or(0),
+                        anchor: Anchor::BottomLeft,
+                        ..Default::default()
+                    },
+                    texture_atlas: textures.mrmotext.clone(),
+                    transform: Transform::from_xyz(CHAR * i as f32, 0., 0.),
+                    ..Default::default()
+                });
+            }
+        })
+        .insert(TextUI)
+        .id()
+}
+
+/// Text on a line from the top of the screen.
+#[derive(Component)]
+struct StatusLine(usize);
+
+fn status_line(line: usize, screen: &Screen) -> Vec3 {
+    Vec3::new(LEFT_MARGIN, screen.height - CHAR * (line + 1) as f32, util::z::TEXT)
+}
+
+fn anchor_status(
+    screen: Res<Screen>,
+    mut lines: Query<(&StatusLine, &mut Transform)>,
+) {
+    if !screen.is_changed() { return; }
+    for (StatusLine(line), mut transform) in lines.iter_mut() {
+        transform.translation = status_line(*line, &screen);
+    }
+}
+
+fn show_message(
+    mut commands: Commands,
+    mut messages: EventReader<ShowMessage>,
+    mut current: Local<Option<(Entity, Timer)>>,
+    textures: Res<Textures>,
+    screen: Res<Screen>,
+    time: Res<Time>,
+) {
+    for ShowMessage(text) in messages.iter() {
+        info!("{}", text);
+        if let Some((e, _)) = current.take() { commands.entity(e).despawn_recursive(); }
+        let e = spawn_text(&mut commands, &textures, text, status_line(0, &screen), Palette::A.color(), Palette::E.color());
+        commands.entity(e).insert(StatusLine(0));
+        *current = Some((e, Timer::from_seconds(util::misc::MESSAGE_SECONDS, TimerMode::Once)));
+    }
+
+    if let Some((e, ref mut timer)) = *current {
+        if timer.tick(time.delta()).finished() {
+            commands.entity(e).despawn_recursive();
+            *current = None;
+        }
+    }
+}
+
+fn open_prompt(
+    mut commands: Commands,
+    mut ev: EventReader<OpenPrompt>,
+    prompt: Option<Res<Prompt>>,
+    textures: Res<Textures>,
+    screen: Res<Screen>,
+) {
+    let Some(OpenPrompt { kind, label, default }) = ev.iter().last() else { return };
+    if let Some(prompt) = prompt { commands.entity(prompt.entity).despawn_recursive(); }
+
+    let entity = spawn_prompt(&mut commands, &textures, &screen, label, default);
+    commands.insert_resource(Prompt {
+        kind: *kind,
+        label: label.clone(),
+        input: default.clone(),
+        entity,
+    });
+}
+
+fn spawn_prompt(commands: &mut Commands, textures: &Res<Textures>, screen: &Screen, label: &str, input: &str) -> Entity {
+    let text = format!("{} {}_", label, input);
+    let e = spawn_text(commands, textures, &text, status_line(1, screen), Palette::A.color(), Palette::L.color());
+    commands.entity(e).insert(StatusLine(1)).id()
+}
+
+fn update_prompt(
+    mut commands: Commands,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut submitted: EventWriter<PromptSubmitted>,
+    mut keys: ResMut<Input<KeyCode>>,
+    prompt: Option<ResMut<Prompt>>,
+    textures: Res<Textures>,
+    screen: Res<Screen>,
+) {
+    let Some(mut prompt) = prompt else { chars.clear(); return };
+
+    let mut changed = false;
+    for ReceivedCharacter { char, .. } in chars.iter() {
+        if char.is_control() { continue }
+        prompt.input.push(*char);
+        changed = true;
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        prompt.input.pop();
+        changed = true;
+    }
+
+    if keys.clear_just_pressed(KeyCode::Return) {
+        submitted.send(PromptSubmitted(prompt.kind, prompt.input.trim().to_string()));
+        commands.entity(prompt.entity).despawn_recursive();
+        commands.remove_resource::<Prompt>();
+    } else if keys.clear_just_pressed(KeyCode::Escape) {
+        commands.entity(prompt.entity).despawn_recursive();
+        commands.remove_resource::<Prompt>();
+    } else if changed {
+        commands.entity(prompt.entity).despawn_recursive();
+        prompt.entity = spawn_prompt(&mut commands, &textures, &screen, &prompt.label, &prompt.input);
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<TextUI>>,
+) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    commands.remove_resource::<Prompt>();
+}
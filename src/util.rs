@@ -1,16 +1,23 @@
 use bevy::prelude::{Color, KeyCode};
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 
 pub mod tool_priority {
     pub const PENCIL: u16 = 0;
+    pub const SHAPE: u16 = 5;
+    pub const FRAME: u16 = 7;
     pub const FILL: u16 = 10;
     pub const ERASER: u16 = 20;
     pub const SELECT: u16 = 30;
     pub const PICK: u16 = 40;
     pub const TEXT: u16 = 50;
     pub const RESIZE: u16 = 60;
+    pub const LAYERS: u16 = 65;
+    pub const TILESET: u16 = 67;
     pub const EXPORT: u16 = 70;
     pub const IMPORT: u16 = 80;
+    pub const OPEN: u16 = 90;
+    pub const SAVE: u16 = 100;
 }
 
 pub mod size {
@@ -19,24 +26,60 @@ pub mod size {
     pub const GRID_Y: usize = 6;
     pub const LEFT_MARGIN: f32 = 32.;
     pub const BOTTOM_MARGIN: f32 = 40.;
+    pub const MAX_GRID: usize = 1024;
+    pub const CHAR: f32 = 8.;
+    /// Area of the quick tiles, filled with as many tiles of the tileset as fit.
+    pub const QUICK_TILES_W: f32 = 256.;
+    pub const QUICK_TILES_H: f32 = 32.;
 }
 
 pub mod z {
     pub const GRID: f32 = 0.;
+    /// Between two layers, [super::misc::MAX_LAYERS] of them stay under [GRID_FLOATING].
+    pub const LAYER_STEP: f32 = 0.01;
+    pub const GRID_FLOATING: f32 = 0.25;
     pub const GRID_HOVER: f32 = 0.5;
+    pub const GRID_OUTLINE: f32 = 0.75;
     pub const TOOLBAR: f32 = 1.;
     pub const TOOLBAR_ICONS_BG: f32 = 1.5;
     pub const TOOLBAR_ICONS: f32 = 2.;
+    pub const PALETTE_EDITOR_BG: f32 = 2.5;
+    pub const PALETTE_EDITOR: f32 = 2.75;
+    pub const TEXT: f32 = 3.;
 }
 
 pub mod misc {
     pub const DEFAULT_TILE: usize = 1;
-    pub const TILESET_COUNT: usize = 1024;
-    pub const QUICK_TILES_ROWS: usize = 4;
-    pub const QUICK_TILES_PER_ROW: usize = 32;
+    pub const PALETTE_SLOTS: usize = 18;
+    pub const TOOLBAR_ROWS: usize = 7;
+    pub const ASSETS: &str = "assets";
+    pub const TILESET: &str = "MRMOTEXT EX.png";
+    pub const DEFAULT_TILESET: &str = "MRMOTEXT EX";
+    pub const TILESETS: &str = "tilesets";
+    pub const PROJECT_EXTENSION: &str = "rtemo";
+    pub const DEFAULT_PROJECT: &str = "drawing.rtemo";
+    pub const DEFAULT_PNG: &str = "drawing.png";
+    pub const DEFAULT_ANS: &str = "drawing.ans";
+    pub const MESSAGE_SECONDS: f32 = 3.;
+    pub const HISTORY_SIZE: usize = 100;
+    pub const MAX_LAYERS: usize = 16;
+    pub const KEYMAP: &str = "keymap.ron";
+    pub const DEFAULT_PALETTE: &str = "palette.hex";
+    /// Columns between tab stops of the text tool, from where typing started.
+    pub const TAB_WIDTH: usize = 4;
+    /// Levels of the zoom in and out commands, 1 is 100%.
+    pub const ZOOM_STEPS: &[f32] = &[0.25, 0.5, 0.75, 1., 1.5, 2., 3., 4.];
+    /// Text screens offered for new drawings.
+    pub const PRESETS: &[(&str, usize, usize)] = &[
+        ("c64", 40, 25),
+        ("dos", 80, 25),
+        ("dos50", 80, 50),
+        ("zx", 32, 24),
+        ("vic20", 22, 23),
+    ];
 }
 
-#[derive(EnumIter, Copy, Clone, Eq, Hash, PartialEq, Debug)]
+#[derive(EnumIter, Copy, Clone, Eq, Hash, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Palette {
     A,
     B,
@@ -83,6 +126,11 @@ impl Palette {
     }
 }
 
+pub fn color_to_hex(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_f32().map(|c| (c * 255.).round() as u8);
+    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+}
+
 pub fn get_char(code: &KeyCode) -> Option<char> {
     match code {
         KeyCode::A => Some('a'),
@@ -116,6 +164,7 @@ pub fn get_char(code: &KeyCode) -> Option<char> {
     }
 }
 
+/// Glyph of the interface font, drawings use [crate::tilesets::Charmap].
 pub fn char_to_tile(c: char) -> Option<usize> {
     let start = b'!' as usize;
     let start_index: usize = 865;
@@ -134,4 +183,5 @@ pub type BG = usize;
 pub type FG = usize;
 pub type FLIP = bool;
 pub type ROTATION = u8;
-pub type TILE = (X, Y, INDEX, BG, FG, FLIP, ROTATION);
\ No newline at end of file
+/// Horizontal flip comes before rotation, vertical flip was added last.
+pub type TILE = (X, Y, INDEX, BG, FG, FLIP, ROTATION, FLIP);
\ No newline at end of file
@@ -0,0 +1,144 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::{AppState, util};
+use crate::grid::{Grid, Pan, Zoom, ZoomChanged};
+use crate::screen::{Cursor, Screen};
+use crate::tilesets::Tilesets;
+use crate::tools::TextCursorState;
+use crate::ui::{Prompt, ShowMessage};
+use crate::util::size::{BOTTOM_MARGIN, LEFT_MARGIN};
+
+pub struct ZoomPlugin;
+
+impl Plugin for ZoomPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems((on_wheel, on_keys, drag).in_set(OnUpdate(AppState::Editor)))
+            .add_system(cleanup.in_schedule(OnExit(AppState::Editor)));
+    }
+}
+
+/// The grid is being dragged, clicks don't reach it.
+#[derive(Resource)]
+pub struct Panning;
+
+fn area_center(screen: &Screen) -> Vec2 {
+    Vec2::new((LEFT_MARGIN + screen.width) / 2., (BOTTOM_MARGIN + screen.height) / 2.)
+}
+
+/// Zooms keeping the point of the grid under [focus] in place.
+fn zoom_to(
+    zoom: &mut Zoom,
+    pan: &mut Pan,
+    level: f32,
+    focus: Vec2,
+    screen: &Screen,
+    zoom_changed: &mut EventWriter<ZoomChanged>,
+    message: &mut EventWriter<ShowMessage>,
+) {
+    let center = area_center(screen);
+    pan.0 = focus - center - (focus - center - pan.0) * level / zoom.0;
+    zoom.0 = level;
+    zoom_changed.send(ZoomChanged);
+    message.send(ShowMessage(format!("Zoom {}%", (level * 100.).round())));
+}
+
+/// Next level of [util::misc::ZOOM_STEPS] in or out.
+fn step(zoom: f32, zoom_in: bool) -> f32 {
+    let steps = util::misc::ZOOM_STEPS;
+    if zoom_in {
+        steps.iter().copied().find(|s| *s > zoom).unwrap_or(steps[steps.len() - 1])
+    } else {
+        steps.iter().copied().rev().find(|s| *s < zoom).unwrap_or(steps[0])
+    }
+}
+
+/// The mouse wheel zooms around the cursor.
+fn on_wheel(
+    mut wheel: EventReader<MouseWheel>,
+    mut zoom: ResMut<Zoom>,
+    mut pan: ResMut<Pan>,
+    mut zoom_changed: EventWriter<ZoomChanged>,
+    mut message: EventWriter<ShowMessage>,
+    cursor: Res<Cursor>,
+    screen: Res<Screen>,
+) {
+    let scroll = wheel.iter().map(|ev| ev.y).sum::<f32>();
+    if scroll == 0. { return; }
+    let focus = cursor.0.unwrap_or(area_center(&screen));
+    let level = step(zoom.0, scroll > 0.);
+    zoom_to(&mut zoom, &mut pan, level, focus, &screen, &mut zoom_changed, &mut message);
+}
+
+/// Ctrl + = / Ctrl + - zoom in and out, Ctrl + 0 fits the grid to the window, Ctrl + 1 is 100%.
+fn on_keys(
+    keys: Res<Input<KeyCode>>,
+    grid: Option<Res<Grid>>,
+    tilesets: Res<Tilesets>,
+    screen: Res<Screen>,
+    prompt: Option<Res<Prompt>>,
+    mut zoom: ResMut<Zoom>,
+    mut pan: ResMut<Pan>,
+    mut zoom_changed: EventWriter<ZoomChanged>,
+    mut message: EventWriter<ShowMessage>,
+) {
+    let Some(grid) = grid else { return };
+    if prompt.is_some() { return; }
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) { return; }
+
+    let center = area_center(&screen);
+    if keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        let level = step(zoom.0, true);
+        zoom_to(&mut zoom, &mut pan, level, center, &screen, &mut zoom_changed, &mut message);
+    } else if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        let level = step(zoom.0, false);
+        zoom_to(&mut zoom, &mut pan, level, center, &screen, &mut zoom_changed, &mut message);
+    } else if keys.just_pressed(KeyCode::Key1) {
+        zoom_to(&mut zoom, &mut pan, 1., center, &screen, &mut zoom_changed, &mut message);
+    } else if keys.just_pressed(KeyCode::Key0) {
+        // Half a cell more horizontally, the grid is drawn off center by as much
+        let tile = tilesets.active().tile_size();
+        let fit = ((screen.width - LEFT_MARGIN) / (tile.x * (grid.w as f32 + 0.5)))
+            .min((screen.height - BOTTOM_MARGIN) / (tile.y * grid.h as f32));
+        let steps = util::misc::ZOOM_STEPS;
+        pan.0 = Vec2::ZERO;
+        zoom_to(&mut zoom, &mut pan, fit.clamp(steps[0], steps[steps.len() - 1]), center, &screen, &mut zoom_changed, &mut message);
+    }
+}
+
+/// The middle button, or Space and the left button, drag the grid.
+fn drag(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    prompt: Option<Res<Prompt>>,
+    text_cursor: Option<Res<TextCursorState>>,
+    panning: Option<Res<Panning>>,
+    mut pan: ResMut<Pan>,
+    mut zoom_changed: EventWriter<ZoomChanged>,
+    mut last: Local<Option<Vec2>>,
+    cursor: Res<Cursor>,
+) {
+    let space = keys.pressed(KeyCode::Space) && prompt.is_none() && text_cursor.is_none();
+    let grabbed = mouse.pressed(MouseButton::Middle) || space;
+    if grabbed != panning.is_some() {
+        if grabbed { commands.insert_resource(Panning); } else { commands.remove_resource::<Panning>(); }
+    }
+
+    let dragging = mouse.pressed(MouseButton::Middle) || (space && mouse.pressed(MouseButton::Left));
+    let position = cursor.0.filter(|_| dragging);
+    if let (Some(position), Some(previous)) = (position, *last) {
+        if position != previous {
+            pan.0 += position - previous;
+            zoom_changed.send(ZoomChanged);
+        }
+    }
+    *last = position;
+}
+
+fn cleanup(
+    mut commands: Commands,
+) {
+    commands.remove_resource::<Panning>();
+}